@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use drive::fruit::collision::{Collider, CollisionEvent, candidate_pairs, solve_contacts};
+use drive::fruit::pva::Position;
+use drive::fruit::{Fruit, FruitCatalogue, FruitType, PhysicsConfig};
+
+const SIZES: [usize; 3] = [100, 500, 2000];
+
+/// `n` fruit of the three smallest tiers, packed about as tightly as a full
+/// container, so the number of touching pairs grows with `n`, not `n²`.
+fn circles(n: usize) -> Vec<(FruitType, Vec2)> {
+    let mut rng = StdRng::seed_from_u64(n as u64);
+    let side = (n as f32).sqrt() * 30.;
+    (0..n)
+        .map(|_| {
+            let typ = FruitType(rng.random_range(0..3));
+            let pos = Vec2::new(rng.random_range(0. ..side), rng.random_range(0. ..side));
+            (typ, pos)
+        })
+        .collect()
+}
+
+fn world(n: usize) -> World {
+    let mut world = World::new();
+    let mut time = Time::<()>::default();
+    time.advance_by(Duration::from_micros(15_625));
+    world.insert_resource(time);
+    world.init_resource::<FruitCatalogue>();
+    world.init_resource::<PhysicsConfig>();
+    world.init_resource::<Events<CollisionEvent>>();
+    for (typ, pos) in circles(n) {
+        let mut fruit = Fruit::new(typ);
+        fruit.pos = Position(pos);
+        world.spawn((fruit, Collider));
+    }
+    world
+}
+
+fn bench_solve_contacts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve_contacts");
+    for n in SIZES {
+        // Every sample starts from a freshly packed pile; run on the same one,
+        // later samples would time a pile that has relaxed and gone to sleep.
+        let setup = || {
+            let mut world = world(n);
+            let mut schedule = Schedule::default();
+            schedule.add_systems(solve_contacts);
+            schedule.initialize(&mut world).unwrap();
+            (world, schedule)
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            b.iter_batched(setup, |(mut world, mut schedule)| {
+                schedule.run(&mut world);
+                world
+            }, BatchSize::LargeInput);
+        });
+    }
+    group.finish();
+}
+
+fn bench_candidate_pairs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("candidate_pairs");
+    let catalogue = FruitCatalogue::default();
+    for n in SIZES {
+        let circles: Vec<(Vec2, f32)> = circles(n).into_iter()
+            .map(|(typ, pos)| (pos, catalogue[typ].radius))
+            .collect();
+        group.bench_with_input(BenchmarkId::from_parameter(n), &circles, |b, circles| {
+            b.iter(|| candidate_pairs(circles));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_solve_contacts, bench_candidate_pairs);
+criterion_main!(benches);
@@ -0,0 +1,291 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::fruit::Fruit;
+use crate::fruit::collision::{Collider, Sleep};
+use crate::fruit::hold::HoldSlot;
+use crate::fruit::input::{DigitalInput, Player};
+use crate::fruit::physics::PhysicsConfig;
+use crate::fruit::pva::{Acceleration, Position, PreviousPosition, Velocity};
+use crate::fruit::queue::NextFruitQueue;
+use crate::fruit::rng::{DropCount, FruitRng, RngState};
+use crate::fruit::score::Score;
+use crate::fruit::toa::{Alpha, Omega, PreviousTheta, Theta};
+use crate::fruit::typ::{FruitCatalogue, FruitType};
+use crate::fruit::world::TOP;
+
+/// A game frozen between two fixed ticks: every fruit in the container and
+/// everything that decides what the player gets next. Everything but `fruits`
+/// can be left out of a hand-written board.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Board {
+    pub fruits: Vec<BoardFruit>,
+    pub player: FruitType,
+    pub input: DigitalInput,
+    pub score: Score,
+    pub drops: u32,
+    pub hold: HoldSlot,
+    /// Upcoming fruit, front first. Drawn from `rng` if left out.
+    pub queue: Vec<FruitType>,
+    /// Carries on from the running game's generator if left out.
+    pub rng: Option<RngState>,
+}
+
+/// One fruit in the container. It starts at rest and awake if only its type
+/// and position are given.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoardFruit {
+    pub typ: FruitType,
+    pub pos: (f32, f32),
+    #[serde(default)]
+    pub vel: (f32, f32),
+    #[serde(default)]
+    pub acc: (f32, f32),
+    #[serde(default)]
+    pub theta: f32,
+    #[serde(default)]
+    pub omega: f32,
+    #[serde(default)]
+    pub alpha: f32,
+    /// Seconds it has been nearly still, and whether that was long enough to
+    /// fall asleep.
+    #[serde(default)]
+    pub idle: f32,
+    #[serde(default)]
+    pub asleep: bool,
+}
+
+/// Replace the board on the next fixed tick, after any reset.
+#[derive(Debug, Event, Clone, PartialEq)]
+pub struct LoadBoardEvent(pub Board);
+
+/// Where F9 saves the board and F10 loads it from.
+#[derive(Debug, Resource, Clone, PartialEq, Deref)]
+pub struct BoardFile(pub PathBuf);
+
+impl Board {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let board: Self = ron::from_str(&text).map_err(io::Error::other)?;
+        if !board.input.is_valid() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "board input keys must be single digits"));
+        }
+        Ok(board)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, Default::default()).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    pub fn take(world: &mut World) -> Self {
+        let fruits = world
+            .query_filtered::<(&FruitType, &Position, &Velocity, &Acceleration, &Theta, &Omega, &Alpha, &Sleep), With<Collider>>()
+            .iter(world)
+            .map(|(&typ, pos, vel, acc, theta, omega, alpha, sleep)| BoardFruit {
+                typ,
+                pos: pos.0.into(),
+                vel: vel.0.into(),
+                acc: acc.0.into(),
+                theta: **theta,
+                omega: **omega,
+                alpha: **alpha,
+                idle: sleep.idle,
+                asleep: sleep.asleep,
+            })
+            .collect();
+        let (player, input) = world
+            .query_filtered::<(&FruitType, &DigitalInput), With<Player>>()
+            .single(world)
+            .map(|(&typ, input)| (typ, input.clone()))
+            .unwrap_or_default();
+        Self {
+            fruits,
+            player,
+            input,
+            score: *world.resource::<Score>(),
+            drops: **world.resource::<DropCount>(),
+            hold: *world.resource::<HoldSlot>(),
+            queue: world.resource::<NextFruitQueue>().iter().collect(),
+            rng: Some(world.resource::<FruitRng>().state()),
+        }
+    }
+
+    /// Swap the game in `world` for this board.
+    pub fn restore(&self, world: &mut World) {
+        let entities: Vec<Entity> = world.query_filtered::<Entity, With<Collider>>().iter(world).collect();
+        for entity in entities {
+            world.despawn(entity);
+        }
+        for fruit in &self.fruits {
+            let pos = Vec2::from(fruit.pos);
+            world.spawn((
+                Fruit {
+                    typ: fruit.typ,
+                    pos: Position(pos),
+                    pre: PreviousPosition(pos),
+                    vel: Velocity(fruit.vel.into()),
+                    acc: Acceleration(fruit.acc.into()),
+                    theta: Theta(fruit.theta),
+                    pre_theta: PreviousTheta(fruit.theta),
+                    omega: Omega(fruit.omega),
+                    alpha: Alpha(fruit.alpha),
+                },
+                Sleep { idle: fruit.idle, asleep: fruit.asleep },
+                Transform::from_translation(pos.extend(0.)),
+                Collider,
+            ));
+        }
+
+        let x = self.input.to_x(world.resource::<PhysicsConfig>());
+        let y = TOP + world.resource::<FruitCatalogue>()[self.player].radius;
+        let players: Vec<Entity> = world.query_filtered::<Entity, With<Player>>().iter(world).collect();
+        for player in players {
+            world.entity_mut(player).insert((self.player, self.input.clone(), Transform::from_xyz(x, y, 0.)));
+        }
+
+        *world.resource_mut::<Score>() = self.score;
+        **world.resource_mut::<DropCount>() = self.drops;
+        *world.resource_mut::<HoldSlot>() = self.hold;
+        if let Some(state) = self.rng {
+            world.insert_resource(FruitRng::from_state(state));
+        }
+        world.resource_mut::<NextFruitQueue>().set(self.queue.iter().copied());
+    }
+}
+
+pub fn load_board(world: &mut World) {
+    let board = world.resource_mut::<Events<LoadBoardEvent>>().drain().last();
+    if let Some(LoadBoardEvent(board)) = board {
+        info!("loaded a board of {} fruit", board.fruits.len());
+        board.restore(world);
+    }
+}
+
+pub fn board_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    file: Res<BoardFile>,
+    mut load_writer: EventWriter<LoadBoardEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        let path = file.0.clone();
+        commands.queue(move |world: &mut World| {
+            match Board::take(world).save(&path) {
+                Ok(()) => info!("saved the board to {}", path.display()),
+                Err(error) => warn!("could not save the board to {}: {error}", path.display()),
+            }
+        });
+    }
+    if keyboard_input.just_pressed(KeyCode::F10) {
+        match Board::load(&file) {
+            Ok(board) => {
+                load_writer.write(LoadBoardEvent(board));
+            }
+            Err(error) => warn!("could not load the board from {}: {error}", file.display()),
+        }
+    }
+}
+
+/// Start the first game from the board file, if there is one.
+pub fn load_board_file(
+    file: Res<BoardFile>,
+    mut load_writer: EventWriter<LoadBoardEvent>,
+    mut loaded: Local<bool>,
+) {
+    if std::mem::replace(&mut *loaded, true) {
+        return;
+    }
+    match Board::load(&file) {
+        Ok(board) => {
+            load_writer.write(LoadBoardEvent(board));
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => warn!("could not load the board from {}: {error}", file.display()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::fruit::sim::FruitSim;
+
+    const DT: Duration = Duration::from_micros(15_625);
+
+    #[test]
+    fn test_board_round_trip() {
+        let mut sim = FruitSim::new(7, DT);
+        for x in [-200., -50., 100., 250.] {
+            sim.drop_at(x);
+            sim.run(25);
+        }
+        // Long enough for the pile to go to sleep.
+        sim.run(300);
+        let board = sim.board();
+        assert_eq!(board.fruits.len(), sim.fruits().len());
+        assert!(board.fruits.iter().any(|fruit| fruit.asleep));
+
+        let text = ron::ser::to_string_pretty(&board, Default::default()).unwrap();
+        let parsed: Board = ron::from_str(&text).unwrap();
+        assert_eq!(ron::ser::to_string_pretty(&parsed, Default::default()).unwrap(), text);
+
+        let mut copy = FruitSim::new(0, DT);
+        copy.load_board(board);
+        copy.step();
+        sim.step();
+        assert_eq!(copy.fruits().len(), sim.fruits().len());
+        for (a, b) in copy.fruits().iter().zip(sim.fruits()) {
+            assert_eq!((a.typ, a.pos, a.vel, a.omega), (b.typ, b.pos, b.vel, b.omega));
+        }
+        assert_eq!(copy.board().fruits, sim.board().fruits);
+        assert_eq!(copy.queue(), sim.queue());
+        assert_eq!(copy.player(), sim.player());
+        assert_eq!(copy.score().points, sim.score().points);
+        assert_eq!(copy.drops(), sim.drops());
+    }
+
+    #[test]
+    fn test_cherry_stack_by_the_wall() {
+        let physics = PhysicsConfig::default();
+        let board: Board = ron::from_str(r#"(
+            fruits: [
+                (typ: 1, pos: (-286.0, -286.0)),
+                (typ: 1, pos: (-286.0, -258.0)),
+                (typ: 1, pos: (-286.0, -230.0)),
+                (typ: 1, pos: (-286.0, -202.0)),
+                (typ: 1, pos: (-286.0, -174.0)),
+                (typ: 1, pos: (-286.0, -146.0)),
+            ],
+        )"#).unwrap();
+
+        let mut sim = FruitSim::new(0, DT);
+        sim.load_board(board);
+        sim.run(300);
+        let fruits = sim.fruits();
+        assert!(!fruits.is_empty());
+        for fruit in fruits {
+            let radius = sim.catalogue()[fruit.typ].radius;
+            assert!(fruit.pos.x - radius > physics.left - 1., "{fruit:?}");
+            assert!(fruit.pos.y - radius > physics.bottom - 1., "{fruit:?}");
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_bad_input() {
+        let path = std::env::temp_dir().join("drive_test_board_bad_input.ron");
+        for keys in [r#"["-5"]"#, r#"["55"]"#, r#"["x"]"#, r#"[""]"#] {
+            fs::write(&path, format!("(fruits: [], input: (keys: {keys}))")).unwrap();
+            assert_eq!(Board::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData, "{keys}");
+        }
+        fs::write(&path, r#"(fruits: [], input: (keys: ["0", "7"]))"#).unwrap();
+        assert_eq!(Board::load(&path).unwrap().input.to_string(), "0.07");
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,79 +1,237 @@
 use bevy::prelude::*;
 
-use crate::fruit::world::{LEFT, RIGHT, BOTTOM, TOP};
-use crate::fruit::pva::{Acceleration, Position, Velocity};
-use crate::fruit::reset::ResetEvent;
-use crate::fruit::toa::{Alpha, Omega, Theta};
-use crate::fruit::typ::FruitType;
-
-const SPRING: f32 = 1e2;
-const DAMPER: f32 = 1e1;
-const BOUNCE: f32 = 1.3;
+use crate::fruit::danger::Danger;
+use crate::fruit::physics::PhysicsConfig;
+use crate::fruit::pva::{Acceleration, Position, PreviousPosition, Velocity};
+use crate::fruit::toa::{Alpha, Omega};
+use crate::fruit::typ::{FruitCatalogue, FruitType};
 
 #[derive(Component, Default)]
+#[require(Danger, Sleep)]
 pub struct Collider;
 
 #[derive(Event, Deref, DerefMut)]
-pub struct CollisionEvent([(Entity, FruitType, Position, Velocity, Acceleration); 2]);
+pub struct CollisionEvent([(Entity, FruitType, Position, Velocity, Acceleration, Omega); 2]);
 
-pub fn check_wall_collisions(
-    collider_query: Query<(
-        &FruitType,
-        &mut Position,
-        &mut Velocity,
-        &mut Acceleration,
-        &mut Theta,
-        &mut Omega,
-    ), With<Collider>>,
-    mut reset_writer: EventWriter<ResetEvent>,
+/// How long a fruit has been nearly still, and whether it has been still long
+/// enough to drop out of the solver. A sleeping fruit holds its place until an
+/// awake fruit knocks into it or a merge changes the pile.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct Sleep {
+    pub idle: f32,
+    pub asleep: bool,
+}
+
+impl Sleep {
+    pub fn wake(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The thinnest overlap a swept fruit is left at, so the solver still sees the
+/// contact when `slop` is zero.
+const MIN_SKIN: f32 = 0.01;
+
+/// A fruit as the contact solver sees it. Sleeping fruit have no inverse mass,
+/// so they hold still like the walls.
+struct Body {
+    inv_mass: f32,
+    inv_inertia: f32,
+    radius: f32,
+    pos: Vec2,
+    vel: Vec2,
+    omega: f32,
+}
+
+/// A touching pair: body `a` and either body `b` or a wall. The normal points
+/// from `a` toward whatever it touches.
+struct Contact {
+    a: usize,
+    b: Option<usize>,
+    normal: Vec2,
+    depth: f32,
+    /// Separating speed the normal impulse aims for, from bouncing.
+    target: f32,
+    /// Impulses summed over the iterations so far.
+    normal_impulse: f32,
+    tangent_impulse: f32,
+}
+
+impl Body {
+    /// Velocity of the point on the edge facing `normal`.
+    fn edge_vel(&self, normal: Vec2) -> Vec2 {
+        self.vel + normal.perp() * self.omega * self.radius
+    }
+
+    /// Push the body by `impulse` at the edge facing `normal`.
+    fn apply(&mut self, normal: Vec2, impulse: Vec2) {
+        self.vel += impulse * self.inv_mass;
+        self.omega += normal.perp_dot(impulse) * self.radius * self.inv_inertia;
+    }
+}
+
+impl Contact {
+    fn new(a: usize, b: Option<usize>, normal: Vec2, depth: f32) -> Self {
+        Self { a, b, normal, depth, target: 0., normal_impulse: 0., tangent_impulse: 0. }
+    }
+
+    fn relative_vel(&self, bodies: &[Body]) -> Vec2 {
+        let a = bodies[self.a].edge_vel(self.normal);
+        let b = self.b.map_or(Vec2::ZERO, |b| bodies[b].edge_vel(-self.normal));
+        b - a
+    }
+
+    /// How easily the contact gives way to an impulse along `normal` and along
+    /// the surface.
+    fn give(&self, bodies: &[Body]) -> (f32, f32) {
+        let give = |body: &Body| (body.inv_mass, body.inv_mass + body.radius * body.radius * body.inv_inertia);
+        let (mut along_normal, mut along_surface) = give(&bodies[self.a]);
+        if let Some(b) = self.b {
+            let (normal, surface) = give(&bodies[b]);
+            along_normal += normal;
+            along_surface += surface;
+        }
+        (along_normal, along_surface)
+    }
+
+    fn apply(&self, bodies: &mut [Body], impulse: Vec2) {
+        bodies[self.a].apply(self.normal, -impulse);
+        if let Some(b) = self.b {
+            bodies[b].apply(-self.normal, impulse);
+        }
+    }
+
+    /// One sequential-impulse pass: stop the pair closing faster than it
+    /// should, then stop it sliding as far as friction allows. Impulses are
+    /// clamped as running totals so later passes can take back what earlier
+    /// ones overdid, but never pull the pair together.
+    fn solve(&mut self, bodies: &mut [Body], friction: f32) {
+        let (normal_give, surface_give) = self.give(bodies);
+        if normal_give <= 0. {
+            return;
+        }
+
+        let closing = self.relative_vel(bodies).dot(self.normal);
+        let impulse = accumulate_impulse(&mut self.normal_impulse, (self.target - closing) / normal_give, 0., f32::INFINITY);
+        self.apply(bodies, self.normal * impulse);
+
+        let tangent = self.normal.perp();
+        let slide = self.relative_vel(bodies).dot(tangent);
+        let limit = friction * self.normal_impulse;
+        let impulse = accumulate_impulse(&mut self.tangent_impulse, -slide / surface_give, -limit, limit);
+        self.apply(bodies, tangent * impulse);
+    }
+
+    /// Slow the turning of both fruit by rolling resistance, which grows with
+    /// how hard the contact pushes. Friction turns the lost spin into lost
+    /// speed on the next tick.
+    fn roll(&self, bodies: &mut [Body], rolling: f32) {
+        let torque = rolling * self.normal_impulse;
+        for i in [Some(self.a), self.b].into_iter().flatten() {
+            let body = &mut bodies[i];
+            let brake = (torque * body.inv_inertia).min(body.omega.abs());
+            body.omega -= brake * body.omega.signum();
+        }
+    }
+
+    /// Move the pair apart by part of the overlap beyond the slop, shared out
+    /// by inverse mass so momentum is untouched.
+    fn separate(&self, bodies: &mut [Body], physics: &PhysicsConfig) {
+        let (give, _) = self.give(bodies);
+        let push = physics.correction * (self.depth - physics.slop).max(0.);
+        if give <= 0. || push <= 0. {
+            return;
+        }
+        let shift = self.normal * push / give;
+        bodies[self.a].pos -= shift * bodies[self.a].inv_mass;
+        if let Some(b) = self.b {
+            bodies[b].pos += shift * bodies[b].inv_mass;
+        }
+    }
+}
+
+/// Add `impulse` to the running `total` at a contact, keeping the total
+/// between `min` and `max`, and return how much of it to apply. Clamping the
+/// total rather than each impulse is what lets a later pass take back part of
+/// an earlier one.
+pub(crate) fn accumulate_impulse(total: &mut f32, impulse: f32, min: f32, max: f32) -> f32 {
+    let clamped = (*total + impulse).clamp(min, max);
+    let applied = clamped - *total;
+    *total = clamped;
+    applied
+}
+
+/// Swept-circle continuous collision detection. A fruit fast enough to cross a
+/// wall or another fruit within one tick is pulled back along its path to where
+/// it first touched, just inside the slop, so [`solve_contacts`] sees the
+/// contact instead of missing it.
+pub fn sweep_collisions(
+    physics: Res<PhysicsConfig>,
+    catalogue: Res<FruitCatalogue>,
+    mut collider_query: Query<(&FruitType, &PreviousPosition, &mut Position), With<Collider>>,
 ) {
-    for (fruit, mut pos, mut vel, mut acc, mut theta, mut omega) in collider_query {
-        let radius = fruit.to_circle().radius;
-        let spin_edge_vel = **omega * radius;
-
-        let right_squish = radius - (RIGHT - pos.x);
-        let x_force = if right_squish > 0. {
-            pos.x = RIGHT - radius;
-            let edge_slip = vel.y + spin_edge_vel;
-            vel.y -= edge_slip;
-            **omega -= edge_slip / radius;
-            -right_squish * SPRING - vel.x * DAMPER
-        } else {
-            let left_squish = radius - (pos.x - LEFT);
-            if left_squish > 0. {
-                pos.x = LEFT + radius;
-                let edge_slip = vel.y - spin_edge_vel;
-                vel.y -= edge_slip;
-                **omega += edge_slip / radius;
-                left_squish * SPRING - vel.x * DAMPER
-            } else {
-                0.0
+    let skin = physics.slop.max(MIN_SKIN);
+    let paths: Vec<(Vec2, Vec2, f32)> = collider_query.iter()
+        .map(|(fruit, pre, pos)| (**pre, **pos, catalogue[*fruit].radius))
+        .collect();
+    // Each fruit moves on from `pre` by this share of its step.
+    let mut impact: Vec<f32> = vec![1.; paths.len()];
+
+    for (i, &(pre, pos, radius)) in paths.iter().enumerate() {
+        // How far past touching each wall the fruit is, before and after.
+        let walls = [
+            (radius - (physics.right - pre.x), radius - (physics.right - pos.x)),
+            (radius - (pre.x - physics.left), radius - (pos.x - physics.left)),
+            (radius - (pre.y - physics.bottom), radius - (pos.y - physics.bottom)),
+        ];
+        for (before, after) in walls {
+            if before < skin && after > skin {
+                impact[i] = impact[i].min((skin - before) / (after - before));
             }
-        };
-        if x_force != 0. {
-            acc.x += x_force / fruit.mass();
         }
+    }
 
-        let bottom_squish = radius - (pos.y - BOTTOM);
-        if bottom_squish > 0. {
-            pos.y = BOTTOM + radius;
-            let edge_slip = vel.x - spin_edge_vel;
-            vel.x -= edge_slip;
-            **omega += edge_slip / radius;
-            let y_force = bottom_squish * SPRING - vel.y * DAMPER;
-            vel.y = 0.;
-            acc.y += y_force / fruit.mass();
-            acc.x -= vel.x * DAMPER * 0.1;
+    // Pairs whose paths come near each other, by the circle around each path.
+    let circles: Vec<(Vec2, f32)> = paths.iter()
+        .map(|&(pre, pos, radius)| ((pre + pos) / 2., radius + pre.distance(pos) / 2.))
+        .collect();
+    for (i, j) in candidate_pairs(&circles) {
+        let (pre0, pos0, radius0) = paths[i];
+        let (pre1, pos1, radius1) = paths[j];
+        // Seen from the second fruit, the first moves from `start` by `step`
+        // and touches once it is `reach` away.
+        let start = pre0 - pre1;
+        let step = (pos0 - pos1) - start;
+        let reach = radius0 + radius1 - skin;
+        let a = step.length_squared();
+        let b = 2. * start.dot(step);
+        let c = start.length_squared() - reach * reach;
+        let disc = b * b - 4. * a * c;
+        if c <= 0. || a <= 0. || disc < 0. {
+            continue;
+        }
+        let t = (-b - disc.sqrt()) / (2. * a);
+        if (0. ..1.).contains(&t) {
+            impact[i] = impact[i].min(t);
+            impact[j] = impact[j].min(t);
         }
+    }
 
-        let top_squish = radius - (TOP - pos.y);
-        if top_squish > radius {
-            reset_writer.write(ResetEvent);
+    for ((_, pre, mut pos), t) in collider_query.iter_mut().zip(impact) {
+        if t < 1. {
+            pos.0 = pre.lerp(pos.0, t);
         }
     }
 }
 
-pub fn check_fruit_collisions(
+/// Find every contact between fruit and with the walls, then settle them all
+/// together with a few rounds of sequential impulses. Fruit that stay nearly
+/// still for `sleep_time` fall asleep and are left out until something wakes
+/// them.
+pub fn solve_contacts(
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+    catalogue: Res<FruitCatalogue>,
     mut collisions: EventWriter<CollisionEvent>,
     mut collider_query: Query<(
         Entity,
@@ -81,54 +239,319 @@ pub fn check_fruit_collisions(
         &mut Position,
         &mut Velocity,
         &mut Acceleration,
-        &mut Theta,
         &mut Omega,
+        &mut Alpha,
+        &mut Sleep,
     ), With<Collider>>,
 ) {
-    let mut combinations = collider_query.iter_combinations_mut();
-    while let Some([
-        (entity0, fruit0, mut pos0, mut vel0, mut acc0, mut theta0, mut omega0),
-        (entity1, fruit1, mut pos1, mut vel1, mut acc1, mut theta1, mut omega1),
-    ]) = combinations.fetch_next() {
-        let radius0 = fruit0.to_circle().radius;
-        let radius1 = fruit1.to_circle().radius;
-
-        let seg = Segment2d::new(pos0.0, pos1.0);
-        let overlap = radius0 + radius1 - seg.length();
-        if overlap > 0. && overlap < radius0 + radius1 {
-            // collision!
-            collisions.write(CollisionEvent([
-                (entity0, fruit0.clone(), pos0.clone(), vel0.clone(), acc0.clone()),
-                (entity1, fruit1.clone(), pos1.clone(), vel1.clone(), acc1.clone())
-            ]));
-
-            fn reaction(toward_other: Vec2, overlap: f32, fruit: &FruitType, pos: &mut Position, vel: &mut Velocity, acc: &mut Acceleration, omega: &mut Omega) {
-                let radius = fruit.radius();
-                let contact_point = **pos + toward_other * (radius - overlap / 2.);
-                let vel_toward_other = toward_other * vel.dot(toward_other).max(0.);
-                let tangential_vel = **vel - vel_toward_other;
-                let spinning_edge_velocity = toward_other.perp() * **omega * radius;
-                let slip_velocity = toward_other.perp_dot(spinning_edge_velocity + tangential_vel);
-
-                // dbg!(toward_other, vel_toward_other, tangential_vel, spinning_edge_velocity, slip_velocity);
-                let squish = toward_other * (overlap / 2.);
-                let spring_force = squish / radius * SPRING;
-                let damp_force = vel_toward_other * DAMPER;
-
-                pos.0 -= squish;
-                vel.0 -= vel_toward_other * BOUNCE;
-                acc.0 -= (spring_force + damp_force) / fruit.mass();
-                **omega -= slip_velocity / radius;
-                // **vel -= slip_velocity;
+    let dt = time.delta_secs();
+    let mut entities = vec![];
+    let mut asleep = vec![];
+    let mut bodies = vec![];
+    for (entity, fruit, pos, vel, _, omega, _, sleep) in &collider_query {
+        let tier = &catalogue[*fruit];
+        entities.push(entity);
+        asleep.push(sleep.asleep);
+        bodies.push(Body {
+            inv_mass: 1. / tier.mass(),
+            inv_inertia: 1. / tier.inertia(),
+            radius: tier.radius,
+            pos: **pos,
+            vel: **vel,
+            omega: **omega,
+        });
+    }
+
+    // Touching pairs, waking sleepers that an awake fruit knocks into.
+    let circles: Vec<(Vec2, f32)> = bodies.iter().map(|body| (body.pos, body.radius)).collect();
+    let mut touching = vec![];
+    for (i, j) in candidate_pairs(&circles) {
+        let seg = Segment2d::new(bodies[i].pos, bodies[j].pos);
+        let overlap = bodies[i].radius + bodies[j].radius - seg.length();
+        if overlap > 0. && overlap < bodies[i].radius + bodies[j].radius {
+            if let Ok([
+                (entity0, fruit0, pos0, vel0, acc0, omega0, ..),
+                (entity1, fruit1, pos1, vel1, acc1, omega1, ..),
+            ]) = collider_query.get_many([entities[i], entities[j]]) {
+                collisions.write(CollisionEvent([
+                    (entity0, *fruit0, *pos0, *vel0, *acc0, *omega0),
+                    (entity1, *fruit1, *pos1, *vel1, *acc1, *omega1),
+                ]));
+            }
+            let knocks = |body: &Body| body.vel.length() > physics.sleep_speed || overlap > 2. * physics.slop;
+            if asleep[i] && !asleep[j] && knocks(&bodies[j]) {
+                asleep[i] = false;
+            } else if asleep[j] && !asleep[i] && knocks(&bodies[i]) {
+                asleep[j] = false;
             }
+            touching.push((i, j, seg.direction().as_vec2(), overlap));
+        }
+    }
+    for (body, &asleep) in bodies.iter_mut().zip(&asleep) {
+        if asleep {
+            body.inv_mass = 0.;
+            body.inv_inertia = 0.;
+            body.vel = Vec2::ZERO;
+            body.omega = 0.;
+        }
+    }
+
+    let mut contacts = vec![];
+    for (i, body) in bodies.iter().enumerate() {
+        if asleep[i] {
+            continue;
+        }
+        let walls = [
+            (Vec2::X, body.radius - (physics.right - body.pos.x)),
+            (Vec2::NEG_X, body.radius - (body.pos.x - physics.left)),
+            (Vec2::NEG_Y, body.radius - (body.pos.y - physics.bottom)),
+        ];
+        for (normal, depth) in walls {
+            if depth > 0. {
+                contacts.push(Contact::new(i, None, normal, depth));
+            }
+        }
+    }
+    for (i, j, normal, depth) in touching {
+        if !(asleep[i] && asleep[j]) {
+            contacts.push(Contact::new(i, Some(j), normal, depth));
+        }
+    }
+
+    // Only bounce off contacts closing faster than gravity alone would close
+    // them in a tick, so resting fruit don't jitter.
+    let resting = 2. * physics.gravity.abs() * dt;
+    for contact in &mut contacts {
+        let closing = contact.relative_vel(&bodies).dot(contact.normal);
+        if closing < -resting {
+            contact.target = -physics.bounce * closing;
+        }
+    }
+    for _ in 0..physics.iterations {
+        for contact in &mut contacts {
+            contact.solve(&mut bodies, physics.friction);
+        }
+    }
+    for contact in &contacts {
+        contact.roll(&mut bodies, physics.rolling);
+        contact.separate(&mut bodies, &physics);
+    }
 
-            let toward_other = seg.direction().as_vec2();
-            reaction(toward_other, overlap, &fruit0, &mut pos0, &mut vel0, &mut acc0, &mut omega0);
+    for (i, body) in bodies.iter().enumerate() {
+        let Ok((_, _, mut pos, mut vel, mut acc, mut omega, mut alpha, mut sleep)) = collider_query.get_mut(entities[i]) else {
+            continue;
+        };
+        if asleep[i] {
+            sleep.asleep = true;
+            vel.0 = Vec2::ZERO;
+            acc.0 = Vec2::ZERO;
+            **omega = 0.;
+            **alpha = 0.;
+            continue;
+        }
+        if sleep.asleep {
+            // Knocked awake this tick.
+            sleep.wake();
+        }
+        pos.0 = body.pos;
+        vel.0 = body.vel;
+        **omega = body.omega;
 
-            let toward_other = seg.direction().as_vec2() * -1.;
-            reaction(toward_other, overlap, &fruit1, &mut pos1, &mut vel1, &mut acc1, &mut omega1);
+        let still = body.vel.length() < physics.sleep_speed && body.omega.abs() * body.radius < physics.sleep_speed;
+        if still {
+            sleep.idle += dt;
+            sleep.asleep = sleep.idle >= physics.sleep_time;
+        } else {
+            sleep.wake();
         }
     }
+}
+
+/// Wake every fruit, for when the pile changes under sleeping fruit: a merge
+/// takes away what they rest on, and new physics can pull them another way.
+pub fn wake_all(
+    query: Query<&mut Sleep>,
+) {
+    for mut sleep in query {
+        sleep.wake();
+    }
+}
 
+/// Sweep and prune: every pair of circles whose bounding boxes overlap, as
+/// indices into `circles` with the lower first. Pairs come out in the order
+/// `iter_combinations` would visit them, so the narrow phase resolves contacts
+/// in the same order it always has.
+pub fn candidate_pairs(circles: &[(Vec2, f32)]) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..circles.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (pos_a, r_a) = circles[a];
+        let (pos_b, r_b) = circles[b];
+        (pos_a.x - r_a).total_cmp(&(pos_b.x - r_b))
+    });
+    let mut pairs = vec![];
+    for (k, &a) in order.iter().enumerate() {
+        let (pos_a, r_a) = circles[a];
+        for &b in &order[k + 1..] {
+            let (pos_b, r_b) = circles[b];
+            if pos_b.x - r_b > pos_a.x + r_a {
+                break;
+            }
+            if (pos_a.y - pos_b.y).abs() <= r_a + r_b {
+                pairs.push((a.min(b), a.max(b)));
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
 }
 
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::fruit::Fruit;
+    use crate::fruit::sim::FruitSim;
+
+    #[test]
+    fn test_fruit_rolls_along_floor() {
+        let mut sim = FruitSim::new(0, Duration::from_micros(15_625));
+        let physics = PhysicsConfig::default();
+        let typ = FruitType(3);
+        let radius = sim.catalogue()[typ].radius;
+        let mut fruit = Fruit::new(typ);
+        fruit.pos = Position(Vec2::new(physics.left + 2. * radius, physics.bottom + radius));
+        fruit.pre.0 = fruit.pos.0;
+        fruit.vel = Velocity(Vec2::new(100., 0.));
+        sim.app_mut().world_mut().spawn((fruit, Collider));
+        sim.run(64);
+
+        let fruit = sim.fruits()[0];
+        assert!(fruit.vel.x > 0.);
+        assert!(fruit.theta < 0., "rolling right turns clockwise");
+        let slip = fruit.vel.x + fruit.omega * radius;
+        assert!(slip.abs() < 1., "slip {slip}");
+    }
+
+    #[test]
+    fn test_collision_conserves_momentum() {
+        let mut sim = FruitSim::new(0, Duration::from_micros(15_625));
+        sim.app_mut().insert_resource(PhysicsConfig { gravity: 0., ..default() });
+        for (typ, x, vel) in [(FruitType(2), -60., 80.), (FruitType(4), 40., -30.)] {
+            let mut fruit = Fruit::new(typ);
+            fruit.pos = Position(Vec2::new(x, 0.));
+            fruit.pre.0 = fruit.pos.0;
+            fruit.vel = Velocity(Vec2::new(vel, 5.));
+            sim.app_mut().world_mut().spawn((fruit, Collider));
+        }
+        let totals = |sim: &mut FruitSim| {
+            let catalogue = sim.catalogue().clone();
+            sim.fruits().iter().fold((Vec2::ZERO, 0.), |(momentum, energy), fruit| {
+                let tier = &catalogue[fruit.typ];
+                (
+                    momentum + fruit.vel * tier.mass(),
+                    energy + 0.5 * tier.mass() * fruit.vel.length_squared() + 0.5 * tier.inertia() * fruit.omega * fruit.omega,
+                )
+            })
+        };
+        let (momentum, energy) = totals(&mut sim);
+        sim.run(90);
+        let (after, energy_after) = totals(&mut sim);
+        assert!(after.distance(momentum) < 1e-3 * momentum.length(), "{momentum} became {after}");
+        assert!(energy_after < energy, "energy went from {energy} to {energy_after}");
+        let fruits = sim.fruits();
+        assert!(fruits[0].vel.x < fruits[1].vel.x, "they bounced apart");
+    }
+
+    #[test]
+    fn test_pile_settles_and_sleeps() {
+        let mut sim = FruitSim::new(3, Duration::from_micros(15_625));
+        for i in 0..24 {
+            sim.drop_at((i % 5) as f32 * 40. - 80.);
+            sim.run(30);
+        }
+        sim.run(600);
+
+        let world = sim.app_mut().world_mut();
+        assert!(world.query::<&Sleep>().iter(world).all(|sleep| sleep.asleep));
+        let physics = PhysicsConfig::default();
+        for fruit in sim.fruits() {
+            let radius = sim.catalogue()[fruit.typ].radius;
+            assert_eq!(fruit.vel, Vec2::ZERO);
+            assert!(fruit.pos.y - radius > physics.bottom - 1., "{fruit:?}");
+            assert!(fruit.pos.x - radius > physics.left - 1. && fruit.pos.x + radius < physics.right + 1., "{fruit:?}");
+        }
+
+        // Something landing on the pile wakes what it hits.
+        let pile: Vec<Entity> = sim.fruits().iter().map(|fruit| fruit.entity).collect();
+        sim.drop_at(0.);
+        let mut woken = false;
+        for _ in 0..300 {
+            sim.step();
+            let world = sim.app_mut().world_mut();
+            woken |= pile.iter().any(|&entity| world.get::<Sleep>(entity).is_some_and(|sleep| !sleep.asleep));
+        }
+        assert!(woken);
+    }
+
+    #[test]
+    fn test_fast_fruit_stays_in_container() {
+        // A low fixed rate, so a blueberry crosses several of its widths a tick.
+        let mut sim = FruitSim::new(0, Duration::from_millis(50));
+        let physics = PhysicsConfig::default();
+        let typ = FruitType(0);
+        let radius = sim.catalogue()[typ].radius;
+        let mut fruit = Fruit::new(typ);
+        fruit.pos = Position(Vec2::new(0., -200.));
+        fruit.pre.0 = fruit.pos.0;
+        fruit.vel = Velocity(Vec2::new(3000., -5000.));
+        sim.app_mut().world_mut().spawn((fruit, Collider));
+
+        for _ in 0..200 {
+            sim.step();
+            let fruit = sim.fruits()[0];
+            assert!(fruit.pos.x - radius > physics.left - 1., "{fruit:?}");
+            assert!(fruit.pos.x + radius < physics.right + 1., "{fruit:?}");
+            assert!(fruit.pos.y - radius > physics.bottom - 1., "{fruit:?}");
+        }
+    }
+
+    #[test]
+    fn test_fast_fruit_do_not_pass_through() {
+        let mut sim = FruitSim::new(0, Duration::from_millis(50));
+        sim.app_mut().insert_resource(PhysicsConfig { gravity: 0., ..default() });
+        for (x, vel) in [(-100., 4000.), (100., -4000.)] {
+            let mut fruit = Fruit::new(FruitType(0));
+            fruit.pos = Position(Vec2::new(x, 0.));
+            fruit.pre.0 = fruit.pos.0;
+            fruit.vel = Velocity(Vec2::new(vel, 0.));
+            sim.app_mut().world_mut().spawn((fruit, Collider));
+        }
+        sim.step();
+        let fruits = sim.fruits();
+        assert!(fruits.len() == 1 || fruits[0].pos.x < fruits[1].pos.x, "{fruits:?}");
+    }
+
+    #[test]
+    fn test_candidate_pairs_match_brute_force() {
+        let circles: Vec<(Vec2, f32)> = (0..200)
+            .map(|i| {
+                let i = i as f32;
+                (Vec2::new((i * 37.) % 290. - 145., (i * 53.) % 310. - 155.), 5. + (i * 7.) % 20.)
+            })
+            .collect();
+        let mut brute = vec![];
+        for a in 0..circles.len() {
+            for b in a + 1..circles.len() {
+                let (pos_a, r_a) = circles[a];
+                let (pos_b, r_b) = circles[b];
+                if pos_a.distance(pos_b) < r_a + r_b {
+                    brute.push((a, b));
+                }
+            }
+        }
+        let candidates = candidate_pairs(&circles);
+        assert!(brute.iter().all(|pair| candidates.contains(pair)));
+        assert!(candidates.len() < circles.len() * (circles.len() - 1) / 2 / 4);
+    }
+}
@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use bevy::color::palettes::basic::RED;
+
+use crate::fruit::collision::Collider;
+use crate::fruit::pva::{Position, Velocity};
+use crate::fruit::state::InGame;
+use crate::fruit::typ::{FruitCatalogue, FruitType};
+use crate::fruit::physics::PhysicsConfig;
+use crate::fruit::world::TOP;
+
+/// The game ends once a fruit has poked above `y` for `grace` seconds while
+/// moving slower than `settled_speed`. Fruit that are only thrown up by a merge
+/// or still falling after a drop don't count.
+#[derive(Debug, Resource, Clone, Copy, PartialEq)]
+pub struct DangerLine {
+    pub y: f32,
+    pub grace: f32,
+    pub settled_speed: f32,
+}
+
+/// Seconds this fruit has spent settled above the [`DangerLine`].
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default, Deref, DerefMut)]
+pub struct Danger(pub f32);
+
+#[derive(Debug, Event, Clone, Copy, PartialEq)]
+pub struct GameOverEvent;
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct DangerLineMesh;
+
+impl Default for DangerLine {
+    fn default() -> Self {
+        Self {
+            y: TOP - 40.,
+            grace: 2.,
+            settled_speed: 20.,
+        }
+    }
+}
+
+pub fn check_danger(
+    time: Res<Time>,
+    line: Res<DangerLine>,
+    catalogue: Res<FruitCatalogue>,
+    query: Query<(&FruitType, &Position, &Velocity, &mut Danger), With<Collider>>,
+    mut game_over: EventWriter<GameOverEvent>,
+) {
+    for (fruit, pos, vel, mut danger) in query {
+        let above = pos.y + catalogue[*fruit].radius > line.y;
+        let settled = vel.length() < line.settled_speed;
+        if above && settled {
+            let before = **danger;
+            **danger += time.delta_secs();
+            if before < line.grace && **danger >= line.grace {
+                game_over.write(GameOverEvent);
+            }
+        } else {
+            **danger = 0.;
+        }
+    }
+}
+
+/// Draw the [`DangerLine`] across the container, again whenever the
+/// [`PhysicsConfig`] moves its walls.
+pub fn load_danger_line(
+    mut commands: Commands,
+    line: Res<DangerLine>,
+    physics: Res<PhysicsConfig>,
+    drawn: Query<Entity, With<DangerLineMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for entity in drawn {
+        commands.entity(entity).despawn();
+    }
+    let PhysicsConfig { left, right, .. } = *physics;
+    commands.spawn((
+        DangerLineMesh,
+        Mesh2d(meshes.add(Rectangle::new(right - left, 1.))),
+        MeshMaterial2d(materials.add(Color::from(RED).with_alpha(0.5))),
+        Transform::from_xyz((left + right) / 2., line.y, 50.),
+        StateScoped(InGame),
+    ));
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::fruit::sim::FruitSim;
+
+    #[test]
+    fn test_overfilled_container_ends_game() {
+        let mut sim = FruitSim::new(7, Duration::from_micros(15_625));
+        for _ in 0..200 {
+            if sim.is_over() {
+                break;
+            }
+            sim.drop_at(0.);
+            sim.run(20);
+        }
+        assert!(sim.is_over());
+        let catalogue = sim.catalogue().clone();
+        let top = sim.fruits().iter().map(|fruit| fruit.pos.y + catalogue[fruit.typ].radius).fold(f32::MIN, f32::max);
+        assert!(top > DangerLine::default().y);
+    }
+}
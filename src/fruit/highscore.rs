@@ -0,0 +1,238 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::fruit::rng::{DropCount, FruitRng};
+use crate::fruit::score::Score;
+use crate::fruit::typ::FruitType;
+
+const TABLE_SIZE: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub points: u64,
+    pub seed: u64,
+    /// `YYYY-MM-DD`, in UTC.
+    pub date: String,
+    pub biggest: Option<FruitType>,
+    pub drops: u32,
+}
+
+/// A table saved while the ladder was a fixed enum, which names the biggest
+/// fruit instead of giving its tier.
+#[derive(Deserialize)]
+struct NamedHighScores {
+    entries: Vec<NamedHighScore>,
+}
+
+#[derive(Deserialize)]
+struct NamedHighScore {
+    points: u64,
+    seed: u64,
+    date: String,
+    biggest: Option<FruitName>,
+    drops: u32,
+}
+
+/// The fixed ladder from before the catalogue, in tier order.
+#[derive(Deserialize)]
+enum FruitName {
+    Blueberry,
+    Cherry,
+    Apricot,
+    Plum,
+    Orange,
+    Apple,
+    Grapefruit,
+    Honeydew,
+    Basketball,
+    Watermelon,
+}
+
+/// The best games so far, best first, kept in `path` between runs.
+#[derive(Debug, Resource, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    /// Position of the latest game in `entries`, if it made the table.
+    #[serde(skip)]
+    pub latest: Option<usize>,
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    /// `highscores.ron` under the platform data dir, e.g.
+    /// `~/.local/share/drive` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("drive").join("highscores.ron"))
+    }
+
+    /// Read the table from `path`. A missing or unreadable file gives an empty
+    /// table, which overwrites it on the next save.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut scores = path.as_deref()
+            .and_then(|path| {
+                Self::read(path)
+                    .inspect_err(|error| match error.kind() {
+                        io::ErrorKind::NotFound => {}
+                        _ => warn!("ignoring high scores in {}: {error}", path.display()),
+                    })
+                    .ok()
+            })
+            .unwrap_or_default();
+        scores.entries.truncate(TABLE_SIZE);
+        scores.path = path;
+        scores
+    }
+
+    fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(io::Error::other)
+    }
+
+    fn parse(text: &str) -> ron::error::SpannedResult<Self> {
+        ron::from_str(text).or_else(|error| {
+            let Ok(named) = ron::from_str::<NamedHighScores>(text) else {
+                return Err(error);
+            };
+            let entries = named.entries.into_iter()
+                .map(|entry| HighScore {
+                    points: entry.points,
+                    seed: entry.seed,
+                    date: entry.date,
+                    biggest: entry.biggest.map(|name| FruitType(name as usize)),
+                    drops: entry.drops,
+                })
+                .collect();
+            Ok(Self { entries, ..Default::default() })
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = ron::ser::to_string_pretty(self, Default::default()).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    /// Add `entry` if it beats anything in the table, and return its rank.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        let rank = self.entries.iter().position(|other| entry.points > other.points)
+            .unwrap_or(self.entries.len());
+        self.latest = (rank < TABLE_SIZE).then(|| {
+            self.entries.insert(rank, entry);
+            self.entries.truncate(TABLE_SIZE);
+            rank
+        });
+        self.latest
+    }
+}
+
+pub fn record_high_score(
+    score: Res<Score>,
+    rng: Res<FruitRng>,
+    drops: Res<DropCount>,
+    mut high_scores: ResMut<HighScores>,
+) {
+    let entry = HighScore {
+        points: score.points,
+        seed: rng.seed(),
+        date: today(),
+        biggest: score.biggest,
+        drops: **drops,
+    };
+    if high_scores.insert(entry).is_some()
+        && let Err(error) = high_scores.save()
+    {
+        warn!("could not save high scores: {error}");
+    }
+}
+
+fn today() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Days since 1970-01-01 to a proleptic Gregorian date, after Howard Hinnant's
+/// `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(points: u64) -> HighScore {
+        HighScore {
+            points,
+            seed: 0,
+            date: "2025-01-01".to_string(),
+            biggest: None,
+            drops: 0,
+        }
+    }
+
+    #[test]
+    fn test_insert_keeps_best() {
+        let mut scores = HighScores::default();
+        for points in 0..TABLE_SIZE as u64 {
+            assert_eq!(scores.insert(entry(points * 10)), Some(0));
+        }
+        assert_eq!(scores.insert(entry(15)), Some(8));
+        assert_eq!(scores.insert(entry(0)), None);
+        assert_eq!(scores.entries.len(), TABLE_SIZE);
+        assert_eq!(scores.entries[0].points, 90);
+        assert_eq!(scores.entries[TABLE_SIZE - 1].points, 10);
+    }
+
+    #[test]
+    fn test_load_corrupt_file() {
+        let path = std::env::temp_dir().join("drive_test_highscores_corrupt.ron");
+        fs::write(&path, "(entries: [(points: ").unwrap();
+        let scores = HighScores::load(Some(path.clone()));
+        assert!(scores.entries.is_empty());
+        assert_eq!(scores.path, Some(path.clone()));
+        fs::remove_file(path).unwrap();
+
+        let scores = HighScores::load(Some(std::env::temp_dir().join("drive_test_highscores_missing.ron")));
+        assert!(scores.entries.is_empty());
+    }
+
+    #[test]
+    fn test_load_named_fruit() {
+        let scores = HighScores::parse(r#"(entries: [
+            (points: 90, seed: 1, date: "2025-01-01", biggest: Some(Plum), drops: 30),
+            (points: 0, seed: 3, date: "2025-01-03", biggest: None, drops: 1),
+        ])"#).unwrap();
+        let biggest: Vec<_> = scores.entries.iter().map(|entry| entry.biggest).collect();
+        assert_eq!(biggest, [Some(FruitType(3)), None]);
+        assert_eq!(scores.entries[0].points, 90);
+        assert!(HighScores::parse("(entries: [(points: ").is_err());
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(20_454), (2026, 1, 1));
+    }
+}
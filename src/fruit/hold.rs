@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::fruit::input::Player;
+use crate::fruit::queue::{NextFruitQueue, PREVIEW_SCALE};
+use crate::fruit::rng::FruitRng;
+use crate::fruit::state::InGame;
+use crate::fruit::typ::{FruitCatalogue, FruitType};
+use crate::fruit::world::{HudAnchor, TOP};
+
+/// Swap the player's fruit with the held one.
+#[derive(Debug, Event, Clone, PartialEq)]
+pub struct HoldEvent;
+
+/// A fruit put aside by the player. It can be swapped once per drop.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HoldSlot {
+    pub held: Option<FruitType>,
+    pub used: bool,
+}
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct HoldPreview;
+
+pub fn hold_fruit(
+    mut slot: ResMut<HoldSlot>,
+    mut queue: ResMut<NextFruitQueue>,
+    mut rng: ResMut<FruitRng>,
+    catalogue: Res<FruitCatalogue>,
+    player: Single<(&mut FruitType, &mut Transform), With<Player>>,
+    mut hold_event: ResMut<Events<HoldEvent>>,
+) {
+    hold_event.clear();
+    if slot.used {
+        return;
+    }
+    let (mut typ, mut transform) = player.into_inner();
+    let held = slot.held.replace(*typ);
+    *typ = held.unwrap_or_else(|| queue.next(&mut rng, &catalogue));
+    slot.used = true;
+    transform.translation.y = TOP + catalogue[*typ].radius;
+}
+
+pub fn load_hold_preview(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        HoldPreview,
+        FruitType::default(),
+        HudAnchor::new(Vec2::new(0., 1.), Vec2::new(-40., -30.)),
+        Transform::from_scale(Vec3::splat(PREVIEW_SCALE)),
+        Visibility::Hidden,
+        StateScoped(InGame),
+    ));
+}
+
+pub fn update_hold_preview(
+    slot: Res<HoldSlot>,
+    preview: Single<(&mut FruitType, &mut Visibility), With<HoldPreview>>,
+) {
+    if !slot.is_changed() {
+        return;
+    }
+    let (mut typ, mut visibility) = preview.into_inner();
+    match slot.held {
+        Some(held) => {
+            typ.set_if_neq(held);
+            visibility.set_if_neq(Visibility::Inherited);
+        }
+        None => {
+            visibility.set_if_neq(Visibility::Hidden);
+        }
+    }
+}
@@ -2,16 +2,27 @@ use std::collections::BTreeSet;
 use std::fmt::{Display, Formatter, Error};
 
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
 
-use crate::fruit::Fruit;
+use crate::fruit::hold::HoldEvent;
+use crate::fruit::physics::PhysicsConfig;
+use crate::fruit::queue::NextFruitQueue;
 use crate::fruit::reset::ResetEvent;
-use crate::fruit::typ::FruitType;
-use crate::fruit::world::{BOTTOM, LEFT, RIGHT, TOP};
+use crate::fruit::rng::{DropCount, FruitRng};
+use crate::fruit::state::InGame;
+use crate::fruit::typ::{FruitCatalogue, FruitType};
+use crate::fruit::world::{HudAnchor, TOP};
+
+/// Left stick deflection below this is ignored.
+const STICK_DEAD_ZONE: f32 = 0.3;
+/// Left stick deflection past this steps the first digit instead of the last.
+const STICK_COARSE: f32 = 0.9;
 
 #[derive(Component)]
 pub struct Player;
 
-#[derive(Debug, Component, Clone, PartialEq, Default, Deref, DerefMut)]
+#[derive(Debug, Component, Clone, PartialEq, Deref, DerefMut, Serialize, Deserialize)]
 pub struct DigitalInput {
     keys: Vec<String>,
 }
@@ -28,12 +39,37 @@ pub struct DropEvent;
 #[derive(Debug, Event, Clone, PartialEq, Deref, DerefMut)]
 pub struct KeyHoldEvent(KeyCode);
 
+/// How the drop position is picked. Typing digits or nudging them with the
+/// arrow keys aims with the [`DigitalInput`]; moving the mouse or touching the
+/// screen aims at the world x of the pointer, and a click or lifted touch drops.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Default)]
+pub enum AimMode {
+    #[default]
+    Digits,
+    Pointer(f32),
+}
+
 #[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
 pub struct PositionDisplay;
 
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct SeedDisplay;
+
+/// Starts in the middle of the container.
+impl Default for DigitalInput {
+    fn default() -> Self {
+        Self { keys: vec!["5".to_string()] }
+    }
+}
+
 impl DigitalInput {
-    pub fn to_x(&self) -> f32 {
-        LEFT + (RIGHT - LEFT) * self.to_string().parse::<f32>().unwrap()
+    /// Whether every key is one digit, as typing or stepping them leaves them.
+    pub fn is_valid(&self) -> bool {
+        self.keys.iter().all(|key| key.len() == 1 && key.as_bytes()[0].is_ascii_digit())
+    }
+
+    pub fn to_x(&self, physics: &PhysicsConfig) -> f32 {
+        physics.left + (physics.right - physics.left) * self.to_string().parse::<f32>().unwrap()
     }
 
     pub fn add_digit(&mut self, key: KeyCode) {
@@ -62,20 +98,31 @@ impl Display for DigitalInput {
     }
 }
 
+/// Where a fruit of `radius` aimed at `pointer` drops: right under it, but no
+/// closer to a wall than touching it.
+pub fn pointer_x(pointer: f32, radius: f32, physics: &PhysicsConfig) -> f32 {
+    pointer.clamp(physics.left + radius, (physics.right - radius).max(physics.left + radius))
+}
+
+/// `x` across the container, written the way a [`DigitalInput`] shows it.
+pub fn position_text(x: f32, physics: &PhysicsConfig) -> String {
+    format!("{:.3}", (x - physics.left) / (physics.right - physics.left))
+}
+
+/// Spawn the player holding the fruit at the front of the queue.
 pub fn load_player(
     mut commands: Commands,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<ColorMaterial>>,
+    catalogue: Res<FruitCatalogue>,
+    mut rng: ResMut<FruitRng>,
+    mut queue: ResMut<NextFruitQueue>,
 ) {
-    let fruit = Fruit::new(FruitType::Blueberry, meshes, materials);
+    let typ = queue.next(&mut rng, &catalogue);
     commands.spawn((
         Player {},
-        DigitalInput { keys: vec!["5".to_string()] },
-        Transform::from_xyz(0., TOP + fruit.typ.radius(), 0.),
-        fruit,
+        DigitalInput::default(),
+        Transform::from_xyz(0., TOP + catalogue[typ].radius, 0.),
+        typ,
     ));
-    commands.insert_resource(AccumulatedInput(Default::default()));
-    commands.insert_resource(PreviousAccumulatedInput(Default::default()));
 }
 
 
@@ -84,12 +131,31 @@ pub fn load_input_display(
 ) {
     commands.spawn((
         PositionDisplay,
+        StateScoped(InGame),
         Text2d::new(""),
-        Transform::from_xyz(LEFT + 20., BOTTOM - 40., 0.),
+        HudAnchor::new(Vec2::new(0., 0.), Vec2::new(20., -40.)),
     ));
+    commands.spawn((
+        SeedDisplay,
+        StateScoped(InGame),
+        Text2d::new(""),
+        HudAnchor::new(Vec2::new(1., 0.), Vec2::new(-100., -40.)),
+    ));
+}
+
+pub fn update_seed_display(
+    rng: Res<FruitRng>,
+    drops: Res<DropCount>,
+    seed_display: Single<&mut Text2d, With<SeedDisplay>>,
+) {
+    if rng.is_changed() || drops.is_changed() {
+        seed_display.into_inner().0 = format!("seed {}, drop {}", rng.seed(), **drops);
+    }
 }
 
 pub fn player_input(
+    physics: Res<PhysicsConfig>,
+    mut aim: ResMut<AimMode>,
     mut input: ResMut<AccumulatedInput>,
     mut previous_input: ResMut<PreviousAccumulatedInput>,
     digital_input: Single<(&mut DigitalInput, &mut Transform)>,
@@ -97,9 +163,11 @@ pub fn player_input(
     mut set_reset: EventWriter<ResetEvent>,
     mut hold_event: ResMut<Events<KeyHoldEvent>>,
     mut drop_event: EventWriter<DropEvent>,
+    mut hold_fruit: EventWriter<HoldEvent>,
 ) {
     hold_event.update();
     let (mut digital_input, mut transform) = digital_input.into_inner();
+    let before = digital_input.clone();
 
     if input.remove(&KeyCode::Backspace) {
         if input.remove(&KeyCode::ShiftLeft) {
@@ -108,6 +176,9 @@ pub fn player_input(
             digital_input.keys.pop();
         }
     }
+    if input.remove(&KeyCode::ArrowUp) {
+        hold_fruit.write(HoldEvent);
+    }
     if input.remove(&KeyCode::ArrowDown) {
         if previous_input.remove(&KeyCode::ArrowDown) {
             hold_event.send(KeyHoldEvent(KeyCode::ArrowDown));
@@ -122,16 +193,14 @@ pub fn player_input(
         input.remove(&KeyCode::ArrowRight).then_some(1).or_else( || {
             input.remove(&KeyCode::ArrowLeft).then_some(-1)
         }),
-        if input.remove(&KeyCode::ControlLeft) || input.remove(&KeyCode::ControlRight) {
-            0
-        } else if digital_input.is_empty() {
+        if input.remove(&KeyCode::ControlLeft) || input.remove(&KeyCode::ControlRight) || digital_input.is_empty() {
             0
         } else {
             digital_input.len() - 1
         }
     )};
     if let Some(dir) = dir {
-        let mut value = digital_input.is_empty().then_some(0).unwrap_or_else(|| digital_input.remove(index).parse::<i32>().unwrap());
+        let mut value = if digital_input.is_empty() { 0 } else { digital_input.remove(index).parse::<i32>().unwrap() };
         value += dir;
         value = value.clamp(0, 9);
         digital_input.insert(index, value.to_string());
@@ -141,18 +210,65 @@ pub fn player_input(
         digital_input.add_digit(key);
     }
 
-    transform.translation.x = digital_input.to_x();
-    position_display.into_inner().0 = digital_input.to_string();
+    if *digital_input != before {
+        aim.set_if_neq(AimMode::Digits);
+    }
+    if *aim == AimMode::Digits {
+        transform.translation.x = digital_input.to_x(&physics);
+        position_display.into_inner().0 = digital_input.to_string();
+    }
+}
+
+/// Aim at the mouse or a touch once either moves, and drop on a click or when
+/// a touch lifts.
+pub fn pointer_input(
+    physics: Res<PhysicsConfig>,
+    catalogue: Res<FruitCatalogue>,
+    mut aim: ResMut<AimMode>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    player: Single<(&FruitType, &mut Transform), With<Player>>,
+    position_display: Single<&mut Text2d, With<PositionDisplay>>,
+    mut drop_event: EventWriter<DropEvent>,
+) {
+    let (camera, camera_transform) = camera.into_inner();
+    // The cursor only takes over from the digits once it moves.
+    let following = cursor_moved.read().count() > 0 || matches!(*aim, AimMode::Pointer(_));
+    let cursor = window.cursor_position().filter(|_| following);
+    let touch = touches.iter().chain(touches.iter_just_released()).next().map(|touch| touch.position());
+    if let Some(pointer) = touch.or(cursor)
+        && let Ok(world) = camera.viewport_to_world_2d(camera_transform, pointer)
+    {
+        aim.set_if_neq(AimMode::Pointer(world.x));
+    }
+    let AimMode::Pointer(pointer) = *aim else {
+        return;
+    };
+
+    let (typ, mut transform) = player.into_inner();
+    let x = pointer_x(pointer, catalogue[*typ].radius, &physics);
+    transform.translation.x = x;
+    position_display.into_inner().0 = position_text(x, &physics);
+
+    if mouse_input.just_pressed(MouseButton::Left) || touches.any_just_released() {
+        drop_event.write(DropEvent);
+    }
 }
 
 pub fn fast_drop(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
     mut drop_event: EventWriter<DropEvent>,
     mut hold_event: ResMut<Events<KeyHoldEvent>>,
 ) {
     let event = hold_event.drain().find(|event|**event == KeyCode::ArrowDown);
     if event.is_some() {
-        if keyboard_input.pressed(KeyCode::ArrowDown) {
+        let held = keyboard_input.pressed(KeyCode::ArrowDown)
+            || gamepads.iter().any(|gamepad| gamepad_keys(gamepad).contains(&KeyCode::ArrowDown));
+        if held {
             drop_event.write(DropEvent);
             hold_event.send(KeyHoldEvent(KeyCode::ArrowDown));
         } else {
@@ -170,3 +286,79 @@ pub fn record_key_press(
     }
 }
 
+/// The keys a gamepad stands in for. The D-pad and left stick step a digit
+/// like the arrow keys: the last one for a fine step, or the first one for a
+/// coarse step, like with Ctrl, while the right shoulder is held or the stick
+/// is pushed all the way. Holding the left shoulder keeps to the fine step
+/// however far the stick goes. South drops, and fast drops while held; east
+/// holds.
+pub fn gamepad_keys(gamepad: &Gamepad) -> Vec<KeyCode> {
+    let mut keys = vec![];
+    let stick = gamepad.left_stick().x;
+    let coarse = gamepad.pressed(GamepadButton::RightTrigger)
+        || (stick.abs() > STICK_COARSE && !gamepad.pressed(GamepadButton::LeftTrigger));
+    if coarse {
+        keys.push(KeyCode::ControlLeft);
+    }
+    if stick < -STICK_DEAD_ZONE || gamepad.pressed(GamepadButton::DPadLeft) {
+        keys.push(KeyCode::ArrowLeft);
+    } else if stick > STICK_DEAD_ZONE || gamepad.pressed(GamepadButton::DPadRight) {
+        keys.push(KeyCode::ArrowRight);
+    }
+    for (button, key) in [
+        (GamepadButton::South, KeyCode::ArrowDown),
+        (GamepadButton::East, KeyCode::ArrowUp),
+    ] {
+        if gamepad.pressed(button) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+pub fn record_gamepad_press(
+    mut input: ResMut<AccumulatedInput>,
+    gamepads: Query<&Gamepad>,
+) {
+    for gamepad in &gamepads {
+        input.extend(gamepad_keys(gamepad));
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pointer_aim_stays_inside_walls() {
+        let physics = PhysicsConfig::default();
+        assert_eq!(pointer_x(-1000., 20., &physics), physics.left + 20.);
+        assert_eq!(pointer_x(1000., 20., &physics), physics.right - 20.);
+        assert_eq!(pointer_x(12.5, 20., &physics), 12.5);
+        assert_eq!(position_text(0., &physics), "0.500");
+        assert_eq!(position_text(pointer_x(1000., 20., &physics), &physics), "0.967");
+    }
+
+    #[test]
+    fn test_gamepad_keys() {
+        let mut gamepad = Gamepad::default();
+        assert!(gamepad_keys(&gamepad).is_empty());
+
+        gamepad.analog_mut().set(GamepadAxis::LeftStickX, 0.5);
+        assert_eq!(gamepad_keys(&gamepad), [KeyCode::ArrowRight]);
+        gamepad.analog_mut().set(GamepadAxis::LeftStickX, -1.);
+        assert_eq!(gamepad_keys(&gamepad), [KeyCode::ControlLeft, KeyCode::ArrowLeft]);
+
+        gamepad.digital_mut().press(GamepadButton::LeftTrigger);
+        assert_eq!(gamepad_keys(&gamepad), [KeyCode::ArrowLeft]);
+        gamepad.digital_mut().release(GamepadButton::LeftTrigger);
+
+        gamepad.analog_mut().set(GamepadAxis::LeftStickX, 0.1);
+        gamepad.digital_mut().press(GamepadButton::DPadRight);
+        gamepad.digital_mut().press(GamepadButton::South);
+        assert_eq!(gamepad_keys(&gamepad), [KeyCode::ArrowRight, KeyCode::ArrowDown]);
+        gamepad.digital_mut().press(GamepadButton::RightTrigger);
+        assert_eq!(gamepad_keys(&gamepad), [KeyCode::ControlLeft, KeyCode::ArrowRight, KeyCode::ArrowDown]);
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy::color::palettes::basic::{AQUA, LIME, RED, YELLOW};
+
+use crate::fruit::collision::{Collider, CollisionEvent};
+use crate::fruit::pva::{Acceleration, Position, Velocity};
+use crate::fruit::state::InGame;
+use crate::fruit::toa::Omega;
+use crate::fruit::typ::{FruitCatalogue, FruitType};
+use crate::fruit::world::HudAnchor;
+
+/// Velocity and acceleration arrows are drawn this many seconds long.
+const ARROW_SECONDS: f32 = 0.25;
+const NORMAL_LENGTH: f32 = 15.;
+
+/// Whether the physics inspector is drawn. Toggled with F3.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Default, Deref, DerefMut)]
+pub struct ShowInspector(pub bool);
+
+/// Every contact between two fruit in the last fixed tick, as the point they
+/// touch and the normal from the first toward the second.
+#[derive(Debug, Resource, Clone, PartialEq, Default, Deref, DerefMut)]
+pub struct Contacts(pub Vec<(Vec2, Vec2)>);
+
+/// Names the fruit it follows.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct InspectorLabel(pub Entity);
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct InspectorPanel;
+
+pub fn toggle_inspector(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut show: ResMut<ShowInspector>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        **show = !**show;
+    }
+}
+
+/// Keep only the contacts of the tick that just ran.
+pub fn record_contacts(
+    catalogue: Res<FruitCatalogue>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut contacts: ResMut<Contacts>,
+) {
+    contacts.clear();
+    for collision in collisions.read() {
+        let (_, fruit0, pos0, ..) = collision[0];
+        let (_, _, pos1, ..) = collision[1];
+        let normal = (*pos1 - *pos0).normalize_or_zero();
+        contacts.push((*pos0 + normal * catalogue[fruit0].radius, normal));
+    }
+}
+
+pub fn load_inspector_panel(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        InspectorPanel,
+        StateScoped(InGame),
+        Text2d::new(""),
+        HudAnchor::new(Vec2::new(0., 0.5), Vec2::new(-170., 0.)),
+        Visibility::Hidden,
+    ));
+}
+
+pub fn draw_inspector(
+    mut gizmos: Gizmos,
+    contacts: Res<Contacts>,
+    query: Query<(&Position, &Velocity, &Acceleration), With<Collider>>,
+) {
+    for (pos, vel, acc) in query {
+        gizmos.arrow_2d(**pos, **pos + **vel * ARROW_SECONDS, LIME);
+        gizmos.arrow_2d(**pos, **pos + **acc * ARROW_SECONDS, RED);
+    }
+    for &(point, normal) in contacts.iter() {
+        gizmos.circle_2d(point, 2., YELLOW);
+        gizmos.line_2d(point, point + normal * NORMAL_LENGTH, AQUA);
+    }
+}
+
+/// Label every fruit with its entity, tier and spin, and sum up the tick in the
+/// side panel. Labels are removed again once the inspector is hidden.
+pub fn update_inspector_labels(
+    mut commands: Commands,
+    show: Res<ShowInspector>,
+    catalogue: Res<FruitCatalogue>,
+    contacts: Res<Contacts>,
+    fruits: Query<(Entity, &FruitType, &Position, &Velocity, &Omega), With<Collider>>,
+    mut labels: Query<(Entity, &InspectorLabel, &mut Text2d, &mut Transform), Without<InspectorPanel>>,
+    panel: Single<(&mut Text2d, &mut Visibility), With<InspectorPanel>>,
+) {
+    let (mut panel_text, mut visibility) = panel.into_inner();
+    visibility.set_if_neq(if **show { Visibility::Inherited } else { Visibility::Hidden });
+
+    let mut labelled = HashSet::new();
+    for (label, &InspectorLabel(fruit), mut text, mut transform) in &mut labels {
+        match fruits.get(fruit) {
+            Ok((_, typ, pos, _, omega)) if **show => {
+                text.0 = label_text(fruit, &catalogue[*typ].name, **omega);
+                transform.translation = pos.extend(2.);
+                labelled.insert(fruit);
+            }
+            _ => commands.entity(label).despawn(),
+        }
+    }
+    if !**show {
+        return;
+    }
+
+    let mut kinetic_energy = 0.;
+    for (fruit, typ, pos, vel, omega) in &fruits {
+        let tier = &catalogue[*typ];
+        kinetic_energy += 0.5 * tier.mass() * vel.length_squared() + 0.5 * tier.inertia() * **omega * **omega;
+        if !labelled.contains(&fruit) {
+            commands.spawn((
+                InspectorLabel(fruit),
+                StateScoped(InGame),
+                Text2d::new(label_text(fruit, &tier.name, **omega)),
+                TextFont::from_font_size(10.),
+                Transform::from_translation(pos.extend(2.)),
+            ));
+        }
+    }
+    panel_text.0 = format!("collisions {}\nkinetic energy {kinetic_energy:.0}", contacts.len());
+}
+
+fn label_text(fruit: Entity, name: &str, omega: f32) -> String {
+    format!("{fruit}\n{name}\nω {omega:.1}")
+}
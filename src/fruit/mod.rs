@@ -1,135 +1,314 @@
-pub(crate) mod collision;
+pub mod board;
+pub mod collision;
+pub(crate) mod danger;
+pub(crate) mod highscore;
+pub(crate) mod hold;
 pub(crate) mod input;
-pub(crate) mod pva;
+pub(crate) mod inspect;
+pub mod physics;
+pub mod pva;
+pub(crate) mod queue;
+pub(crate) mod replay;
 pub(crate) mod reset;
-pub(crate) mod toa;
-pub(crate) mod typ;
-pub(crate) mod world;
+pub(crate) mod rng;
+pub(crate) mod score;
+pub(crate) mod sim;
+pub(crate) mod state;
+pub(crate) mod stepper;
+pub mod toa;
+pub mod typ;
+pub mod world;
 
+use std::path::PathBuf;
 use std::time::Duration;
 
 use bevy::prelude::*;
 use bevy::time::common_conditions::on_timer;
 
-use collision::{Collider, CollisionEvent, check_fruit_collisions, check_wall_collisions};
-use input::{DropEvent, KeyHoldEvent, Player, record_key_press, load_player, load_input_display, player_input, fast_drop};
-use pva::{Acceleration, Position, PreviousPosition, Velocity, apply_acceleration, apply_gravity, apply_velocity};
-use reset::{reset, ResetEvent};
-use toa::{Omega, Theta};
-use typ::FruitType;
-use world::{load_container, TOP};
+use board::{BoardFile, board_input, load_board_file};
+use collision::{Collider, CollisionEvent, solve_contacts};
+use danger::load_danger_line;
+use highscore::record_high_score;
+use hold::{load_hold_preview, update_hold_preview};
+use input::{AccumulatedInput, AimMode, DropEvent, KeyHoldEvent, Player, PreviousAccumulatedInput, record_gamepad_press, record_key_press, load_input_display, load_player, player_input, pointer_input, fast_drop, update_seed_display};
+use inspect::{Contacts, ShowInspector, draw_inspector, load_inspector_panel, record_contacts, toggle_inspector, update_inspector_labels};
+use physics::{PhysicsPresetsLoader, load_physics, update_physics};
+use pva::{Acceleration, Position, PreviousPosition, Velocity};
+use queue::{load_queue_preview, update_queue_preview};
+use rng::{DropCount, FruitRng};
+use score::{load_score_display, update_score_display};
+use state::{continue_replay, end_game, game_over_input, load_game_over_screen, load_menu_screen, load_pause_screen, menu_input, pause_input, unload_game, unload_pause_screen};
+use stepper::{History, Stepper, load_stepper_display, record_snapshot, reset_stepper, sim_unfrozen, stepper_input, update_stepper_display, use_step};
+use toa::{Omega, PreviousTheta, Theta};
+use typ::{FruitCatalogueLoader, load_catalogue, update_catalogue};
+use world::{load_camera, load_container, place_hud, TOP};
+
+pub use board::{Board, BoardFruit, LoadBoardEvent};
+pub use danger::{DangerLine, GameOverEvent};
+pub use highscore::{HighScore, HighScores};
+pub use hold::{HoldEvent, HoldSlot};
+pub use physics::{PhysicsConfig, PhysicsPreset, PhysicsPresets, PhysicsPresetsHandle};
+pub use queue::NextFruitQueue;
+pub use replay::{Playback, Recorder, Replay, ReplayAction, ReplayEvent};
+pub use score::Score;
+pub use sim::{FixedTick, FruitSim, FruitSimPlugin, FruitSimSet, FruitState};
+pub use state::{GameState, InGame};
+pub use typ::{FruitCatalogue, FruitCatalogueHandle, FruitTier, FruitType};
 
 const INPUT_RATE_HZ: u64 = 1;
 const REPEAT_RATE_HZ: u64 = 2;
 
-pub struct FruitGame;
+#[derive(Default)]
+pub struct FruitGame {
+    /// Seed for the first game's fruit queue. A random one is picked and logged
+    /// if unset. Later games are seeded from the one before.
+    pub seed: Option<u64>,
+    /// Write every player action to this replay file.
+    pub record: Option<PathBuf>,
+    /// Play this replay file instead of reading the keyboard.
+    pub replay: Option<PathBuf>,
+    /// Where to keep the high-score table, instead of [`HighScores::default_path`].
+    pub high_scores: Option<PathBuf>,
+    /// How many upcoming fruit to preview.
+    pub preview: Option<usize>,
+    /// Name of the physics preset to play with, instead of `"default"`. A
+    /// replay plays with the physics it was recorded with instead. Edits to the
+    /// presets file are ignored while recording or replaying.
+    pub physics: Option<String>,
+    /// Start the first game from this board file if it exists, save the board
+    /// to it with F9 and load it back with F10. Loading a board isn't recorded,
+    /// so don't combine this with `record`.
+    pub board: Option<PathBuf>,
+}
 
 impl Plugin for FruitGame {
     fn build(&self, app: &mut App) {
+        let replay = self.replay.as_ref().and_then(|path| {
+            Replay::load(path)
+                .inspect_err(|error| warn!("could not load replay {}: {error}", path.display()))
+                .ok()
+        });
+        let seed = replay.as_ref().map(|replay| replay.seed)
+            .or(self.seed)
+            .unwrap_or_else(rand::random);
+        info!("fruit seed {seed}");
+        let preset = self.physics.clone().unwrap_or_else(|| "default".to_string());
+        let physics = replay.as_ref().map(|replay| replay.physics).unwrap_or_else(|| {
+            PhysicsConfig::preset(&preset).unwrap_or_else(|| {
+                warn!("no physics preset {preset:?}, using the default");
+                PhysicsConfig::default()
+            })
+        });
+
+        if let Some(replay) = replay {
+            // Replayed games are listed but never saved as high scores.
+            app
+            .insert_resource(HighScores::load(None))
+            .insert_resource(Time::<Fixed>::from_duration(replay.timestep))
+            .insert_resource(Playback::new(replay))
+            .insert_state(GameState::Playing)
+            .add_systems(Update, continue_replay.run_if(in_state(GameState::GameOver)));
+        } else {
+            app
+            .insert_resource(HighScores::load(self.high_scores.clone().or_else(HighScores::default_path)))
+            .init_state::<GameState>()
+            .add_systems(Update, (
+                player_input.run_if(on_timer(Duration::from_millis(1000 / INPUT_RATE_HZ))),
+                pointer_input,
+                fast_drop.run_if(on_timer(Duration::from_millis(1000 / REPEAT_RATE_HZ))),
+            ).run_if(in_state(GameState::Playing)))
+            .add_systems(Update, (
+                menu_input.run_if(in_state(GameState::Menu)),
+                pause_input.run_if(in_state(GameState::Playing).or(in_state(GameState::Paused))),
+                game_over_input.run_if(in_state(GameState::GameOver)),
+            ))
+            .add_systems(FixedUpdate, (record_key_press, record_gamepad_press).run_if(in_state(GameState::Playing)).before(FruitSimSet));
+        }
+        app
+        .insert_resource(physics)
+        .insert_resource(PhysicsPreset(preset));
+
+        if let Some(path) = &self.board {
+            app
+            .insert_resource(BoardFile(path.clone()))
+            .add_systems(OnEnter(InGame), load_board_file)
+            .add_systems(Update, board_input.run_if(in_state(GameState::Playing)));
+        }
+        if let Some(len) = self.preview {
+            app.insert_resource(NextFruitQueue::new(len));
+        }
+        if let Some(path) = &self.record {
+            let timestep = app.world().get_resource::<Time<Fixed>>().map_or_else(|| Time::<Fixed>::default().timestep(), Time::timestep);
+            app.insert_resource(Recorder::new(seed, timestep, physics, Some(path.clone())));
+        }
+
         app
-        .add_systems(Startup, (load_container, load_player, load_input_display))
+        .add_plugins(FruitSimPlugin { seed })
+        .add_computed_state::<InGame>()
+        .enable_state_scoped_entities::<GameState>()
+        .enable_state_scoped_entities::<InGame>()
+        .configure_sets(FixedUpdate, FruitSimSet.run_if(in_state(GameState::Playing).and(sim_unfrozen)))
+        .init_asset::<FruitCatalogue>()
+        .init_asset_loader::<FruitCatalogueLoader>()
+        .init_asset::<PhysicsPresets>()
+        .init_asset_loader::<PhysicsPresetsLoader>()
+        .add_systems(Startup, (load_camera, load_catalogue, load_physics))
+        .add_systems(OnEnter(GameState::Menu), load_menu_screen)
+        .add_systems(OnEnter(InGame), (load_container, load_danger_line, load_player, load_queue_preview, load_hold_preview, load_input_display, load_score_display, load_inspector_panel, load_stepper_display, reset_stepper))
+        .add_systems(OnExit(InGame), unload_game)
+        .add_systems(OnEnter(GameState::Paused), load_pause_screen)
+        .add_systems(OnExit(GameState::Paused), unload_pause_screen)
+        .add_systems(OnEnter(GameState::GameOver), (record_high_score, load_game_over_screen).chain())
         .add_systems(Update, (
-            player_input.run_if(on_timer(Duration::from_millis(1000 / INPUT_RATE_HZ))),
-            fast_drop.run_if(on_timer(Duration::from_millis(1000 / REPEAT_RATE_HZ))),
-        ))
-        .add_systems(FixedUpdate, (
-            record_key_press,
-            drop_fruit.run_if(on_event::<DropEvent>),
-            apply_velocity,
-            apply_acceleration,
-            apply_gravity,
-            check_wall_collisions,
-            check_fruit_collisions,
-            merge,
-        ).chain())
-        .add_systems(RunFixedMainLoop, (
-            interpolate_rendered_transform.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
-            // indicate_spin.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
-            reset.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop).run_if(on_event::<ResetEvent>),
+            (update_catalogue.run_if(on_event::<AssetEvent<FruitCatalogue>>), attach_fruit_mesh).chain(),
+            (
+                // A recording or replay keeps to the physics it started with.
+                update_physics.run_if(on_event::<AssetEvent<PhysicsPresets>>.and(not(resource_exists::<Recorder>)).and(not(resource_exists::<Playback>))),
+                (load_container, load_danger_line).run_if(resource_changed::<PhysicsConfig>.and(in_state(InGame))),
+                place_hud.run_if(in_state(InGame)),
+            ).chain(),
+            (
+                update_seed_display,
+                update_score_display,
+                update_queue_preview,
+                update_hold_preview,
+                end_game.run_if(on_event::<GameOverEvent>),
+                toggle_inspector,
+                (draw_inspector.run_if(resource_equals(ShowInspector(true))), update_inspector_labels).after(toggle_inspector),
+                stepper_input.run_if(in_state(GameState::Playing)),
+                update_stepper_display,
+            ).run_if(in_state(InGame)),
         ))
-        .add_event::<CollisionEvent>()
-        .add_event::<ResetEvent>()
-        .add_event::<DropEvent>()
+        .add_systems(FixedUpdate, record_contacts.after(solve_contacts).in_set(FruitSimSet))
+        .add_systems(FixedUpdate, (record_snapshot, use_step).chain().after(FruitSimSet).run_if(in_state(GameState::Playing).and(sim_unfrozen)))
+        .add_systems(RunFixedMainLoop, interpolate_rendered_transform.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop))
+        .init_resource::<AccumulatedInput>()
+        .init_resource::<PreviousAccumulatedInput>()
+        .init_resource::<AimMode>()
         .init_resource::<Events<KeyHoldEvent>>()
+        .init_resource::<ShowInspector>()
+        .init_resource::<Contacts>()
+        .init_resource::<Stepper>()
+        .init_resource::<History>()
         ;
     }
 }
 
+/// Two fruit merged into one of type `typ` at `pos`.
+#[derive(Debug, Event, Clone, Copy, PartialEq)]
+pub struct MergeEvent {
+    pub typ: FruitType,
+    pub pos: Vec2,
+}
+
 #[derive(Bundle, Clone, Default)]
 pub struct Fruit {
     pub typ: FruitType,
-    pub mesh: Mesh2d,
-    pub material: MeshMaterial2d<ColorMaterial>,
     pub pos: Position,
     pub pre: PreviousPosition,
     pub vel: Velocity,
     pub acc: Acceleration,
     pub theta: Theta,
+    pub pre_theta: PreviousTheta,
     pub omega: Omega,
+    pub alpha: toa::Alpha,
 }
 
+/// A stripe from the middle of a fruit to its edge, so that rolling shows.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct SpinMarker;
+
 impl Fruit {
-    pub fn new(
-        typ: FruitType,
-        mut meshes: ResMut<Assets<Mesh>>,
-        mut materials: ResMut<Assets<ColorMaterial>>,
-    ) -> Self {
+    pub fn new(typ: FruitType) -> Self {
         Self {
             typ,
-            mesh: Mesh2d(meshes.add(typ.to_circle())),
-            material: MeshMaterial2d(materials.add(typ.color().with_alpha(0.5))),
             ..Default::default()
         }
     }
 
-    pub fn rand_to(
-        upper: FruitType,
-        meshes: ResMut<Assets<Mesh>>,
-        materials: ResMut<Assets<ColorMaterial>>,
-    ) -> Self {
-        Self::new(FruitType::rand_up_to(upper), meshes, materials)
+    pub fn rand_to(upper: FruitType, rng: &mut FruitRng, catalogue: &FruitCatalogue) -> Self {
+        Self::new(catalogue.rand_up_to(upper, &mut **rng))
     }
 }
 
 fn drop_fruit(
     mut commands: Commands,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<ColorMaterial>>,
-    query: Single<(
-        &mut FruitType,
-        &mut Mesh2d,
-        &mut MeshMaterial2d<ColorMaterial>,
-        &mut Transform,
-        ), With<Player>>,
+    mut rng: ResMut<FruitRng>,
+    mut queue: ResMut<NextFruitQueue>,
+    mut drops: ResMut<DropCount>,
+    mut hold: ResMut<HoldSlot>,
+    catalogue: Res<FruitCatalogue>,
+    query: Single<(&mut FruitType, &mut Transform), With<Player>>,
     mut drop_event: ResMut<Events<DropEvent>>,
 ) {
     drop_event.clear();
-    let (mut typ, mut mesh, mut material, mut transform) = query.into_inner();
-    let radius = typ.radius();
+    let (mut typ, mut transform) = query.into_inner();
+    let radius = catalogue[*typ].radius;
     let mut spawn_location = *transform;
     spawn_location.translation.y -= radius * 2.;
     let fruit = Fruit {
         typ: *typ,
-        mesh: mesh.clone(),
-        material: material.clone(),
         pos: Position(spawn_location.translation.truncate()),
         pre: PreviousPosition(spawn_location.translation.truncate()),
         vel: Velocity(Vec2::new(0., -100.)),
-        acc: Default::default(),
-        theta: Default::default(),
-        omega: Default::default(),
+        ..Default::default()
     };
     commands.spawn((
         fruit,
         spawn_location,
         Collider,
     ));
+    **drops += 1;
+    hold.used = false;
 
-    let new_fruit = Fruit::rand_to(FruitType::Apricot, meshes, materials);
-    *typ = new_fruit.typ;
-    *mesh = new_fruit.mesh;
-    *material = new_fruit.material;
-    transform.translation.y = TOP + typ.radius();
+    *typ = queue.next(&mut rng, &catalogue);
+    transform.translation.y = TOP + catalogue[*typ].radius;
+}
+
+/// Draw each fruit as its tier's sprite, or a plain circle with a
+/// [`SpinMarker`] if it has none. Everything is redrawn when the catalogue
+/// changes.
+fn attach_fruit_mesh(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    catalogue: Res<FruitCatalogue>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<(Entity, Ref<FruitType>)>,
+) {
+    for (entity, typ) in &query {
+        if !typ.is_changed() && !catalogue.is_changed() {
+            continue;
+        }
+        let tier = &catalogue[*typ];
+        let mut entity = commands.entity(entity);
+        entity.despawn_related::<Children>();
+        match &tier.sprite {
+            Some(path) => {
+                entity
+                    .remove::<(Mesh2d, MeshMaterial2d<ColorMaterial>)>()
+                    .insert(Sprite {
+                        image: asset_server.load(path),
+                        custom_size: Some(Vec2::splat(tier.radius * 2.)),
+                        ..Default::default()
+                    });
+            }
+            None => {
+                entity
+                    .remove::<Sprite>()
+                    .insert((
+                        Mesh2d(meshes.add(tier.to_circle())),
+                        MeshMaterial2d(materials.add(tier.color().with_alpha(0.5))),
+                    ))
+                    .with_child((
+                        SpinMarker,
+                        Mesh2d(meshes.add(Rectangle::new(tier.radius * 0.2, tier.radius))),
+                        MeshMaterial2d(materials.add(Color::BLACK.with_alpha(0.5))),
+                        Transform::from_xyz(0., tier.radius / 2., 0.1),
+                    ));
+            }
+        }
+    }
 }
 
 fn interpolate_rendered_transform(
@@ -138,50 +317,36 @@ fn interpolate_rendered_transform(
         &mut Transform,
         &Position,
         &PreviousPosition,
+        &Theta,
+        &PreviousTheta,
     ), (With<FruitType>, Without<Player>)>,
 ) {
-    for (mut transform, &pos, &pre) in query.iter_mut() {
+    for (mut transform, &pos, &pre, &theta, &pre_theta) in query.iter_mut() {
         // The overstep fraction is a value between 0 and 1 that tells us how far we are between two fixed timesteps.
         let alpha = fixed_time.overstep_fraction();
 
         let rendered_translation = pre.lerp(*pos, alpha);
         transform.translation = rendered_translation.extend(1.0);
+        transform.rotation = Quat::from_rotation_z(pre_theta.lerp(*theta, alpha));
     }
 }
 
-// fn indicate_spin(
-//     mut query: Query<(
-//         &Omega,
-//         &mut MeshMaterial2d<ColorMaterial>,
-//     )>,
-//     mut materials: ResMut<Assets<ColorMaterial>>,
-// ) {
-//     for (&omega, mut material) in query.into_iter() {
-//         let alpha = 0.5 + *omega * 0.05;
-//         // if *omega != 0. {
-//         //     warn!("{omega:?}");
-//         // }
-//         if let Some(mut color_material) = materials.get_mut(&material.0) {
-//             color_material.color.set_alpha(alpha);
-//         }
-//     }
-// }
-
 fn merge(
     mut commands: Commands,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<ColorMaterial>>,
+    catalogue: Res<FruitCatalogue>,
     mut collisions: EventReader<CollisionEvent>,
+    mut merges: EventWriter<MergeEvent>,
 ) {
     for collision in collisions.read() {
-        let (entity0, fruit0, pos0, vel0, _) = collision[0];
-        let (entity1, fruit1, pos1, vel1, _) = collision[1];
+        let (entity0, fruit0, pos0, vel0, _, omega0) = collision[0];
+        let (entity1, fruit1, pos1, vel1, _, omega1) = collision[1];
         if fruit0 != fruit1 {
             continue;
         }
-        if let Some(new_type) = fruit0.next() {
+        if let Some(new_type) = catalogue.next(fruit0) {
             let midpoint = (*pos0 + *pos1) / 2.;
-            let vel = (*vel0 * fruit0.mass() + *vel1 * fruit1.mass()) / new_type.mass();
+            let vel = (*vel0 * catalogue[fruit0].mass() + *vel1 * catalogue[fruit1].mass()) / catalogue[new_type].mass();
+            let omega = (*omega0 * catalogue[fruit0].inertia() + *omega1 * catalogue[fruit1].inertia()) / catalogue[new_type].inertia();
 
             if let Ok(mut e) = commands.get_entity(entity0) {
                 e.despawn();
@@ -190,18 +355,23 @@ fn merge(
                 e.despawn();
             }
 
-            let mut merged_fruit = Fruit::new(new_type, meshes, materials);
+            let mut merged_fruit = Fruit::new(new_type);
             *merged_fruit.pos = midpoint;
             *merged_fruit.pre = midpoint;
             *merged_fruit.vel = vel;
+            *merged_fruit.omega = omega;
             commands.spawn((
                 merged_fruit,
                 Transform::from_xyz(midpoint.x, midpoint.y, 0.),
                 Collider,
             ));
+            merges.write(MergeEvent { typ: new_type, pos: midpoint });
             break;
         }
 
     }
+    // Contacts left unread may name fruit that just merged away. Pairs that
+    // still touch report again next tick.
+    collisions.clear();
 }
 
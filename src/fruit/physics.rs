@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use bevy::asset::{AssetLoader, LoadContext, io::Reader};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Tuning for the fruit simulation. The collision and integration systems read
+/// it every tick, so it can be changed while a game runs.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhysicsConfig {
+    /// Vertical acceleration of every fruit. Negative is down.
+    pub gravity: f32,
+    /// Share of the speed into a fruit or wall that is thrown back, from 0 for
+    /// no bounce to 1 for none lost.
+    pub bounce: f32,
+    /// Friction coefficient: the sideways impulse at a contact is at most this
+    /// times the impulse pushing it apart.
+    pub friction: f32,
+    /// Rolling resistance: how far off centre, in world units, the push at a
+    /// contact acts against turning.
+    pub rolling: f32,
+    /// Rounds of impulses the contact solver runs each tick. More settle tall
+    /// piles more stiffly.
+    pub iterations: u32,
+    /// Share of the overlap beyond `slop` pushed out each tick.
+    pub correction: f32,
+    /// Overlap left alone, so resting contacts stay touching.
+    pub slop: f32,
+    /// Fruit slower than this, and whose edge turns slower than this, are still.
+    pub sleep_speed: f32,
+    /// Seconds a fruit must stay still before it falls asleep.
+    pub sleep_time: f32,
+    pub left: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// Every named [`PhysicsConfig`], loaded from `assets/physics.ron`. The one
+/// called `"default"` is used unless another is picked at startup.
+#[derive(Debug, Asset, TypePath, Clone, PartialEq, Serialize, Deserialize, Deref)]
+#[serde(transparent)]
+pub struct PhysicsPresets(pub BTreeMap<String, PhysicsConfig>);
+
+/// The name of the preset picked at startup.
+#[derive(Debug, Resource, Clone, PartialEq, Eq, Deref)]
+pub struct PhysicsPreset(pub String);
+
+/// The presets asset the game is using.
+#[derive(Debug, Resource, Clone, Deref)]
+pub struct PhysicsPresetsHandle(pub Handle<PhysicsPresets>);
+
+#[derive(Default)]
+pub struct PhysicsPresetsLoader;
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        PhysicsPresets::default()["default"]
+    }
+}
+
+impl PhysicsConfig {
+    /// The named preset from the presets built into the binary.
+    pub fn preset(name: &str) -> Option<Self> {
+        PhysicsPresets::default().get(name).copied()
+    }
+}
+
+impl Default for PhysicsPresets {
+    fn default() -> Self {
+        PhysicsPresets::parse(include_str!("../../assets/physics.ron")).expect("built-in physics presets")
+    }
+}
+
+impl PhysicsPresets {
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let presets: Self = ron::from_str(text).map_err(io::Error::other)?;
+        if !presets.contains_key("default") {
+            return Err(io::Error::other("physics presets have no \"default\""));
+        }
+        for (name, config) in presets.iter() {
+            if !(0. ..=1.).contains(&config.bounce) || !(0. ..=1.).contains(&config.correction) {
+                return Err(io::Error::other(format!("{name} needs a bounce and correction between 0 and 1")));
+            }
+            if !(config.friction >= 0. && config.rolling >= 0. && config.slop >= 0. && config.sleep_speed >= 0. && config.sleep_time >= 0.) {
+                return Err(io::Error::other(format!("{name} needs a non-negative friction, rolling, slop and sleep")));
+            }
+            if config.iterations == 0 {
+                return Err(io::Error::other(format!("{name} needs at least one solver iteration")));
+            }
+            if config.left >= config.right {
+                return Err(io::Error::other(format!("{name} has its left wall right of its right wall")));
+            }
+        }
+        Ok(presets)
+    }
+}
+
+impl AssetLoader for PhysicsPresetsLoader {
+    type Asset = PhysicsPresets;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<PhysicsPresets, io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let text = String::from_utf8(bytes).map_err(io::Error::other)?;
+        PhysicsPresets::parse(&text)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["physics.ron"]
+    }
+}
+
+pub fn load_physics(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(PhysicsPresetsHandle(asset_server.load("physics.ron")));
+}
+
+/// Swap in the picked preset whenever the presets asset finishes loading or is
+/// edited.
+pub fn update_physics(
+    mut events: EventReader<AssetEvent<PhysicsPresets>>,
+    handle: Res<PhysicsPresetsHandle>,
+    preset: Res<PhysicsPreset>,
+    assets: Res<Assets<PhysicsPresets>>,
+    mut physics: ResMut<PhysicsConfig>,
+) {
+    for event in events.read() {
+        if (event.is_loaded_with_dependencies(handle.id()) || event.is_modified(handle.id()))
+            && let Some(loaded) = assets.get(handle.id())
+        {
+            match loaded.get(&**preset) {
+                Some(config) => {
+                    info!("loaded physics preset {}", **preset);
+                    physics.set_if_neq(*config);
+                }
+                None => warn!("physics presets have no {:?}", **preset),
+            }
+        }
+    }
+}
+
+/// Run condition: the [`PhysicsConfig`] is not the one seen last time. Unlike
+/// `resource_changed`, this stays false on the first run, when the config is
+/// only new to the system.
+pub fn physics_changed(
+    physics: Res<PhysicsConfig>,
+    mut last: Local<Option<PhysicsConfig>>,
+) -> bool {
+    last.replace(*physics).is_some_and(|last| last != *physics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        let presets = PhysicsPresets::default();
+        for name in ["default", "floaty", "heavy", "bouncy"] {
+            assert!(presets.contains_key(name), "missing {name}");
+        }
+        assert!(presets["floaty"].gravity > presets["default"].gravity);
+        assert!(presets["heavy"].gravity < presets["default"].gravity);
+        assert!(presets["bouncy"].bounce > presets["default"].bounce);
+    }
+
+    #[test]
+    fn test_rejects_bad_presets() {
+        assert!(PhysicsPresets::parse("{}").is_err());
+        let preset = |fields: &str| format!(r#"{{
+            "default": (gravity: -100.0, friction: 0.5, rolling: 1.0, iterations: 8, correction: 0.2, slop: 0.5, sleep_speed: 5.0, sleep_time: 0.5, bottom: -300.0, {fields}),
+        }}"#);
+        assert!(PhysicsPresets::parse(&preset("bounce: 0.2, left: -300.0, right: 300.0")).is_ok());
+        assert!(PhysicsPresets::parse(&preset("bounce: 0.2, left: 300.0, right: -300.0")).is_err());
+        assert!(PhysicsPresets::parse(&preset("bounce: 1.3, left: -300.0, right: 300.0")).is_err());
+    }
+}
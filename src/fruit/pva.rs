@@ -1,9 +1,8 @@
 use bevy::prelude::*;
 
+use crate::fruit::physics::PhysicsConfig;
 use crate::fruit::typ::FruitType;
 
-const GRAVITY: f32 = -100.;
-
 #[derive(Debug, Component, Clone, Copy, PartialEq, Default, Deref, DerefMut)]
 pub struct Position(pub Vec2);
 
@@ -40,9 +39,10 @@ pub fn apply_acceleration(
 }
 
 pub fn apply_gravity(
+    physics: Res<PhysicsConfig>,
     mut query: Query<&mut Acceleration, With<FruitType>>,
 ) {
     for mut acc in &mut query {
-        acc.0 += Vec2::new(0., GRAVITY);
+        acc.0 += Vec2::new(0., physics.gravity);
     }
 }
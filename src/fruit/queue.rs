@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::fruit::rng::FruitRng;
+use crate::fruit::state::InGame;
+use crate::fruit::typ::{FruitCatalogue, FruitType};
+use crate::fruit::world::HudAnchor;
+
+/// How big the queued and held fruit are drawn, next to their real size.
+pub(crate) const PREVIEW_SCALE: f32 = 0.5;
+
+/// The fruit the player gets after the current one, front first, so they can
+/// plan ahead. Insert one with [`NextFruitQueue::new`] before the sim plugin to
+/// show more or fewer.
+#[derive(Debug, Resource, Clone, PartialEq)]
+pub struct NextFruitQueue {
+    len: usize,
+    fruit: VecDeque<FruitType>,
+}
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Deref)]
+pub struct QueuePreview(pub usize);
+
+impl Default for NextFruitQueue {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl NextFruitQueue {
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            fruit: VecDeque::with_capacity(len + 1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> Option<FruitType> {
+        self.fruit.get(i).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = FruitType> + '_ {
+        self.fruit.iter().copied()
+    }
+
+    pub fn fill(&mut self, rng: &mut FruitRng, catalogue: &FruitCatalogue) {
+        while self.fruit.len() < self.len {
+            self.fruit.push_back(catalogue.rand_up_to(catalogue.largest_drop, &mut **rng));
+        }
+    }
+
+    /// Take the fruit at the front and draw a new one onto the back.
+    pub fn next(&mut self, rng: &mut FruitRng, catalogue: &FruitCatalogue) -> FruitType {
+        self.fill(rng, catalogue);
+        // Draw before taking, so that a zero-length queue still hands out fruit.
+        self.fruit.push_back(catalogue.rand_up_to(catalogue.largest_drop, &mut **rng));
+        self.fruit.pop_front().unwrap()
+    }
+
+    /// Line up exactly these fruit, front first, ahead of any drawn later.
+    pub fn set(&mut self, fruit: impl IntoIterator<Item = FruitType>) {
+        self.fruit = fruit.into_iter().collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.fruit.clear();
+    }
+}
+
+pub fn fill_queue(
+    mut queue: ResMut<NextFruitQueue>,
+    mut rng: ResMut<FruitRng>,
+    catalogue: Res<FruitCatalogue>,
+) {
+    if queue.fruit.len() < queue.len {
+        queue.fill(&mut rng, &catalogue);
+    }
+}
+
+pub fn load_queue_preview(
+    mut commands: Commands,
+    queue: Res<NextFruitQueue>,
+    catalogue: Res<FruitCatalogue>,
+) {
+    let spacing = catalogue[catalogue.largest_drop].radius * PREVIEW_SCALE * 2. + 10.;
+    for i in 0..queue.len() {
+        let y = -(i as f32 + 0.5) * spacing;
+        commands.spawn((
+            QueuePreview(i),
+            queue.get(i).unwrap_or_default(),
+            HudAnchor::new(Vec2::new(1., 1.), Vec2::new(40., y)),
+            Transform::from_scale(Vec3::splat(PREVIEW_SCALE)),
+            Visibility::default(),
+            StateScoped(InGame),
+        ));
+    }
+}
+
+pub fn update_queue_preview(
+    queue: Res<NextFruitQueue>,
+    previews: Query<(&QueuePreview, &mut FruitType, &mut Visibility)>,
+) {
+    if !queue.is_changed() {
+        return;
+    }
+    for (preview, mut typ, mut visibility) in previews {
+        match queue.get(**preview) {
+            Some(next) => {
+                typ.set_if_neq(next);
+                visibility.set_if_neq(Visibility::Inherited);
+            }
+            None => {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
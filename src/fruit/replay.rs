@@ -0,0 +1,211 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::fruit::hold::HoldEvent;
+use crate::fruit::input::{DropEvent, Player};
+use crate::fruit::physics::PhysicsConfig;
+use crate::fruit::reset::ResetEvent;
+use crate::fruit::sim::FixedTick;
+
+/// Everything needed to play a game again: the seed, the fixed timestep, the
+/// physics and every player action keyed by the fixed tick it took effect on.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub timestep: Duration,
+    /// Replays saved before this was recorded play with the default preset.
+    #[serde(default)]
+    pub physics: PhysicsConfig,
+    pub events: Vec<ReplayEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    pub tick: u64,
+    pub action: ReplayAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReplayAction {
+    Drop { x: f32 },
+    Hold,
+    Reset,
+}
+
+/// Appends every player action to a [`Replay`], and writes it to `path` after
+/// each one if set.
+#[derive(Debug, Resource, Default)]
+pub struct Recorder {
+    pub path: Option<PathBuf>,
+    pub replay: Replay,
+}
+
+/// Feeds the actions of a [`Replay`] back into the simulation in place of the
+/// player.
+#[derive(Debug, Resource, Default)]
+pub struct Playback {
+    pub replay: Replay,
+    next: usize,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let replay: Self = ron::from_str(&text).map_err(io::Error::other)?;
+        if replay.timestep.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "replay has a zero timestep"));
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, Default::default()).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+}
+
+impl Recorder {
+    /// Start recording a game seeded with `seed` and run at `timestep` with
+    /// `physics`, which are saved even if the player never acts.
+    pub fn new(seed: u64, timestep: Duration, physics: PhysicsConfig, path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            replay: Replay { seed, timestep, physics, ..Default::default() },
+        }
+    }
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, next: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.replay.events.len()
+    }
+}
+
+pub fn play_actions(
+    tick: Res<FixedTick>,
+    mut playback: ResMut<Playback>,
+    player: Single<&mut Transform, With<Player>>,
+    mut drop_writer: EventWriter<DropEvent>,
+    mut hold_writer: EventWriter<HoldEvent>,
+    mut reset_writer: EventWriter<ResetEvent>,
+) {
+    let mut transform = player.into_inner();
+    while let Some(event) = playback.replay.events.get(playback.next).copied() {
+        if event.tick > **tick {
+            break;
+        }
+        match event.action {
+            ReplayAction::Drop { x } => {
+                transform.translation.x = x;
+                drop_writer.write(DropEvent);
+            }
+            ReplayAction::Hold => {
+                hold_writer.write(HoldEvent);
+            }
+            ReplayAction::Reset => {
+                reset_writer.write(ResetEvent);
+            }
+        }
+        playback.next += 1;
+        if playback.is_finished() {
+            info!("replay finished at tick {}", **tick);
+        }
+    }
+}
+
+pub fn record_actions(
+    tick: Res<FixedTick>,
+    fixed_time: Res<Time<Fixed>>,
+    mut recorder: ResMut<Recorder>,
+    player: Single<&Transform, With<Player>>,
+    mut drops: EventReader<DropEvent>,
+    mut holds: EventReader<HoldEvent>,
+    mut resets: EventReader<ResetEvent>,
+) {
+    let mut actions = vec![];
+    if resets.read().last().is_some() {
+        actions.push(ReplayAction::Reset);
+    }
+    if holds.read().last().is_some() {
+        actions.push(ReplayAction::Hold);
+    }
+    if drops.read().last().is_some() {
+        actions.push(ReplayAction::Drop { x: player.translation.x });
+    }
+    if actions.is_empty() {
+        return;
+    }
+    recorder.replay.timestep = fixed_time.timestep();
+    for action in actions {
+        recorder.replay.events.push(ReplayEvent { tick: **tick, action });
+    }
+}
+
+pub fn save_recording(
+    recorder: Res<Recorder>,
+) {
+    if let Some(path) = &recorder.path
+        && let Err(error) = recorder.replay.save(path)
+    {
+        warn!("could not save replay to {}: {error}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fruit::sim::FruitSim;
+
+    #[test]
+    fn test_replay_reproduces_game() {
+        let dt = Duration::from_micros(15_625);
+        let physics = PhysicsConfig::preset("bouncy").unwrap();
+        let mut sim = FruitSim::new(99, dt);
+        sim.app_mut()
+            .insert_resource(physics)
+            .insert_resource(Recorder::new(99, dt, physics, None));
+        for i in 0..12 {
+            if i % 5 == 2 {
+                sim.hold();
+            }
+            sim.drop_at(-250. + 40. * i as f32);
+            sim.run(25 + i);
+        }
+        sim.reset();
+        sim.run(5);
+        sim.drop_at(17.);
+        sim.run(40);
+
+        let replay = sim.app_mut().world_mut().remove_resource::<Recorder>().unwrap().replay;
+        assert_eq!(replay.events.len(), 16);
+        let text = ron::to_string(&replay).unwrap();
+        let replay: Replay = ron::from_str(&text).unwrap();
+        assert_eq!(replay.physics, physics);
+
+        let mut replayed = FruitSim::from_replay(replay);
+        replayed.run(sim.tick() as usize);
+        assert_eq!(replayed.fruits(), sim.fruits());
+    }
+
+    #[test]
+    fn test_unplayed_recording_replays() {
+        let dt = Duration::from_micros(15_625);
+        let path = std::env::temp_dir().join("drive_test_replay_unplayed.ron");
+        Recorder::new(5, dt, PhysicsConfig::default(), None).replay.save(&path).unwrap();
+        let replay = Replay::load(&path).unwrap();
+        assert_eq!((replay.seed, replay.timestep), (5, dt));
+
+        Replay { seed: 5, ..Default::default() }.save(&path).unwrap();
+        assert_eq!(Replay::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,7 +1,12 @@
 use bevy::prelude::*;
 
 use crate::fruit::collision::Collider;
-use crate::fruit::typ::FruitType;
+use crate::fruit::hold::HoldSlot;
+use crate::fruit::input::Player;
+use crate::fruit::queue::NextFruitQueue;
+use crate::fruit::rng::{DropCount, FruitRng};
+use crate::fruit::score::Score;
+use crate::fruit::typ::{FruitCatalogue, FruitType};
 
 #[derive(Event)]
 pub struct ResetEvent;
@@ -9,10 +14,26 @@ pub struct ResetEvent;
 pub fn reset(
     mut commands: Commands,
     query: Query<Entity, (With<FruitType>, With<Collider>)>,
+    players: Query<&mut FruitType, With<Player>>,
+    mut score: ResMut<Score>,
+    mut rng: ResMut<FruitRng>,
+    mut drops: ResMut<DropCount>,
+    mut queue: ResMut<NextFruitQueue>,
+    mut hold: ResMut<HoldSlot>,
+    catalogue: Res<FruitCatalogue>,
     _reader: EventReader<ResetEvent>,
 ) {
-    warn!("reset");
+    warn!("reset with score {}", score.points);
+    *score = Score::default();
+    rng.next_game();
+    queue.clear();
+    *hold = HoldSlot::default();
+    **drops = 0;
     for entity in query {
         commands.entity(entity).despawn();
     }
+    // The new game's first fruit comes from its own queue, like every other.
+    for mut typ in players {
+        *typ = queue.next(&mut rng, &catalogue);
+    }
 }
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+/// The only source of randomness in the fruit game, so that a seed and the
+/// list of player actions reproduce a game exactly. This is the generator
+/// behind `StdRng`, used directly so that its position can be saved.
+#[derive(Resource, Deref, DerefMut)]
+pub struct FruitRng {
+    seed: u64,
+    #[deref]
+    rng: ChaCha12Rng,
+}
+
+/// Where a [`FruitRng`] is in its stream: its seed and how many words it has
+/// handed out since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RngState {
+    pub seed: u64,
+    pub word_pos: u64,
+}
+
+/// How many fruit have been dropped since the game started.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Default, Deref, DerefMut)]
+pub struct DropCount(pub u32);
+
+impl FruitRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn from_state(state: RngState) -> Self {
+        let mut rng = Self::from_seed(state.seed);
+        rng.rng.set_word_pos(state.word_pos.into());
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn state(&self) -> RngState {
+        RngState {
+            seed: self.seed,
+            word_pos: self.rng.get_word_pos() as u64,
+        }
+    }
+
+    /// Start the next game from a seed drawn from this one, so that every game
+    /// can be reproduced from its own seed. The draw comes from a stream of its
+    /// own, so the next seed doesn't depend on how many fruit this game drew,
+    /// or how far the queue looked ahead.
+    pub fn next_game(&mut self) {
+        let mut seeds = ChaCha12Rng::seed_from_u64(self.seed);
+        seeds.set_stream(1);
+        *self = Self::from_seed(seeds.random());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next_game_ignores_draws() {
+        let mut a = FruitRng::from_seed(1234);
+        let mut b = FruitRng::from_seed(1234);
+        for _ in 0..7 {
+            a.random::<u32>();
+        }
+        a.next_game();
+        b.next_game();
+        assert_eq!(a.seed(), b.seed());
+        assert_ne!(a.seed(), 1234);
+        assert_eq!(a.random::<u64>(), b.random::<u64>());
+    }
+}
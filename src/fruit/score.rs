@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::fruit::MergeEvent;
+use crate::fruit::state::InGame;
+use crate::fruit::typ::{FruitCatalogue, FruitType};
+use crate::fruit::world::HudAnchor;
+
+/// Merges that follow each other within this many seconds build a combo.
+const COMBO_WINDOW: f32 = 1.5;
+
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Score {
+    pub points: u64,
+    /// Length of the current merge chain. Each merge in it scores this many times over.
+    pub combo: u32,
+    pub best_combo: u32,
+    pub biggest: Option<FruitType>,
+    #[serde(skip)]
+    last_merge: Option<f32>,
+}
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct ScoreDisplay;
+
+impl Score {
+    pub fn add_merge(&mut self, typ: FruitType, points: u64, now: f32) {
+        let chained = self.last_merge.is_some_and(|last| now - last <= COMBO_WINDOW);
+        self.combo = if chained { self.combo + 1 } else { 1 };
+        self.best_combo = self.best_combo.max(self.combo);
+        self.biggest = self.biggest.max(Some(typ));
+        self.points += points * self.combo as u64;
+        self.last_merge = Some(now);
+    }
+}
+
+pub fn score_merges(
+    time: Res<Time>,
+    catalogue: Res<FruitCatalogue>,
+    mut score: ResMut<Score>,
+    mut merges: EventReader<MergeEvent>,
+) {
+    for merge in merges.read() {
+        score.add_merge(merge.typ, catalogue[merge.typ].points, time.elapsed_secs());
+    }
+}
+
+pub fn load_score_display(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        ScoreDisplay,
+        StateScoped(InGame),
+        Text2d::new(""),
+        HudAnchor::new(Vec2::new(0.5, 0.), Vec2::new(0., -40.)),
+    ));
+}
+
+pub fn update_score_display(
+    score: Res<Score>,
+    catalogue: Res<FruitCatalogue>,
+    score_display: Single<&mut Text2d, With<ScoreDisplay>>,
+) {
+    if !score.is_changed() && !catalogue.is_changed() {
+        return;
+    }
+    let biggest = score.biggest.map(|typ| catalogue[typ].name.clone()).unwrap_or_else(|| "-".to_string());
+    score_display.into_inner().0 = format!(
+        "score {}  combo x{}  biggest {biggest}",
+        score.points, score.best_combo,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_combo() {
+        let mut score = Score::default();
+        score.add_merge(FruitType(1), 3, 0.);
+        score.add_merge(FruitType(2), 6, 1.);
+        score.add_merge(FruitType(3), 10, 2.);
+        assert_eq!(score.combo, 3);
+        assert_eq!(score.points, 3 + 6 * 2 + 10 * 3);
+
+        score.add_merge(FruitType(1), 3, 10.);
+        assert_eq!(score.combo, 1);
+        assert_eq!(score.best_combo, 3);
+        assert_eq!(score.biggest, Some(FruitType(3)));
+    }
+}
@@ -0,0 +1,348 @@
+use std::time::Duration;
+
+use bevy::ecs::event::EventCursor;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use crate::fruit::{MergeEvent, drop_fruit, merge};
+use crate::fruit::board::{Board, LoadBoardEvent, load_board};
+use crate::fruit::collision::{Collider, CollisionEvent, solve_contacts, sweep_collisions, wake_all};
+use crate::fruit::danger::{DangerLine, GameOverEvent, check_danger};
+use crate::fruit::hold::{HoldEvent, HoldSlot, hold_fruit};
+use crate::fruit::input::{DropEvent, Player, load_player};
+use crate::fruit::physics::{PhysicsConfig, physics_changed};
+use crate::fruit::pva::{Position, Velocity, apply_acceleration, apply_gravity, apply_velocity};
+use crate::fruit::queue::{NextFruitQueue, fill_queue};
+use crate::fruit::replay::{Playback, Recorder, Replay, play_actions, record_actions, save_recording};
+use crate::fruit::reset::{reset, ResetEvent};
+use crate::fruit::rng::{DropCount, FruitRng};
+use crate::fruit::score::{Score, score_merges};
+use crate::fruit::toa::{Omega, Theta, apply_alpha, apply_omega};
+use crate::fruit::typ::{FruitCatalogue, FruitType};
+
+/// The fixed-step simulation, from replaying or recording player actions to
+/// scoring merges. Anything that feeds input into the simulation should run
+/// `.before(FruitSimSet)`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FruitSimSet;
+
+/// How many fixed ticks the simulation has run. Player actions are recorded and
+/// replayed against this.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Eq, Default, Deref)]
+pub struct FixedTick(pub u64);
+
+/// Everything the fruit game needs to simulate, and nothing it needs to draw or
+/// read from a keyboard. Works on top of `MinimalPlugins`. The player is not
+/// spawned here; add `load_player` wherever a game starts.
+pub struct FruitSimPlugin {
+    pub seed: u64,
+}
+
+impl Plugin for FruitSimPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(FruitRng::from_seed(self.seed))
+        .init_resource::<DropCount>()
+        .init_resource::<FixedTick>()
+        .init_resource::<Score>()
+        .init_resource::<DangerLine>()
+        .init_resource::<NextFruitQueue>()
+        .init_resource::<HoldSlot>()
+        .init_resource::<FruitCatalogue>()
+        .init_resource::<PhysicsConfig>()
+        .add_systems(FixedUpdate, ((
+            play_actions.run_if(resource_exists::<Playback>),
+            record_actions.run_if(resource_exists::<Recorder>),
+        ).chain(), (
+            reset.run_if(on_event::<ResetEvent>),
+            load_board.run_if(on_event::<LoadBoardEvent>),
+            fill_queue,
+            hold_fruit.run_if(on_event::<HoldEvent>),
+            drop_fruit.run_if(on_event::<DropEvent>),
+            apply_velocity,
+            apply_omega,
+            apply_acceleration,
+            apply_alpha,
+            apply_gravity,
+            sweep_collisions,
+            solve_contacts,
+            merge,
+            wake_all.run_if(on_event::<MergeEvent>.or(physics_changed)),
+            score_merges,
+            check_danger,
+            advance_tick,
+        ).chain()).chain().in_set(FruitSimSet))
+        .add_systems(Update, save_recording.run_if(resource_exists_and_changed::<Recorder>))
+        .add_event::<CollisionEvent>()
+        .add_event::<ResetEvent>()
+        .add_event::<LoadBoardEvent>()
+        .add_event::<DropEvent>()
+        .add_event::<HoldEvent>()
+        .add_event::<MergeEvent>()
+        .add_event::<GameOverEvent>()
+        ;
+    }
+}
+
+fn advance_tick(
+    mut tick: ResMut<FixedTick>,
+) {
+    tick.0 += 1;
+}
+
+/// A snapshot of one fruit in the container.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FruitState {
+    pub entity: Entity,
+    pub typ: FruitType,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub theta: f32,
+    pub omega: f32,
+}
+
+/// A headless fruit game that advances exactly one fixed tick per [`FruitSim::step`].
+pub struct FruitSim {
+    app: App,
+    game_overs: EventCursor<GameOverEvent>,
+    over: bool,
+}
+
+impl FruitSim {
+    pub fn new(seed: u64, dt: Duration) -> Self {
+        Self::build(seed, dt, |_| {})
+    }
+
+    /// A simulation that plays back `replay` with the seed, timestep and
+    /// physics it was recorded with.
+    pub fn from_replay(replay: Replay) -> Self {
+        Self::build(replay.seed, replay.timestep, |app| {
+            app
+            .insert_resource(replay.physics)
+            .insert_resource(Playback::new(replay));
+        })
+    }
+
+    fn build(seed: u64, dt: Duration, setup: impl FnOnce(&mut App)) -> Self {
+        let mut app = App::new();
+        app
+        .add_plugins(MinimalPlugins)
+        .add_plugins(FruitSimPlugin { seed })
+        .insert_resource(Time::<Fixed>::from_duration(dt))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(dt))
+        .add_systems(Startup, load_player);
+        setup(&mut app);
+        // The first update only starts the clocks and runs `Startup`.
+        app.update();
+        Self {
+            app,
+            game_overs: Default::default(),
+            over: false,
+        }
+    }
+
+    pub fn step(&mut self) {
+        self.app.update();
+        let events = self.app.world().resource::<Events<GameOverEvent>>();
+        if self.game_overs.read(events).count() > 0 {
+            self.over = true;
+        }
+    }
+
+    /// Whether a fruit has stayed above the [`DangerLine`] for too long since
+    /// the start or the last [`FruitSim::reset`].
+    pub fn is_over(&self) -> bool {
+        self.over
+    }
+
+    /// Clear the container and the score, starting a new game on the next step.
+    pub fn reset(&mut self) {
+        self.app.world_mut().send_event(ResetEvent);
+        self.over = false;
+    }
+
+    pub fn run(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.step();
+        }
+    }
+
+    /// Queue a drop of the current player fruit at world position `x`. It is
+    /// spawned on the next [`FruitSim::step`].
+    pub fn drop_at(&mut self, x: f32) {
+        let world = self.app.world_mut();
+        let mut transform = world.query_filtered::<&mut Transform, With<Player>>().single_mut(world).unwrap();
+        transform.translation.x = x;
+        world.send_event(DropEvent);
+    }
+
+    /// Queue a swap of the player fruit with the held one, done on the next
+    /// [`FruitSim::step`].
+    pub fn hold(&mut self) {
+        self.app.world_mut().send_event(HoldEvent);
+    }
+
+    /// Queue a swap of the whole game for `board`, done on the next
+    /// [`FruitSim::step`].
+    pub fn load_board(&mut self, board: Board) {
+        self.app.world_mut().send_event(LoadBoardEvent(board));
+    }
+
+    pub fn board(&mut self) -> Board {
+        Board::take(self.app.world_mut())
+    }
+
+    pub fn held(&self) -> Option<FruitType> {
+        self.app.world().resource::<HoldSlot>().held
+    }
+
+    pub fn player(&mut self) -> FruitType {
+        let world = self.app.world_mut();
+        *world.query_filtered::<&FruitType, With<Player>>().single(world).unwrap()
+    }
+
+    pub fn tick(&self) -> u64 {
+        **self.app.world().resource::<FixedTick>()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.app.world().resource::<FruitRng>().seed()
+    }
+
+    pub fn drops(&self) -> u32 {
+        **self.app.world().resource::<DropCount>()
+    }
+
+    pub fn queue(&self) -> Vec<FruitType> {
+        self.app.world().resource::<NextFruitQueue>().iter().collect()
+    }
+
+    pub fn catalogue(&self) -> &FruitCatalogue {
+        self.app.world().resource::<FruitCatalogue>()
+    }
+
+    pub fn score(&self) -> Score {
+        *self.app.world().resource::<Score>()
+    }
+
+    pub fn fruits(&mut self) -> Vec<FruitState> {
+        let world = self.app.world_mut();
+        world.query_filtered::<(Entity, &FruitType, &Position, &Velocity, &Theta, &Omega), With<Collider>>()
+            .iter(world)
+            .map(|(entity, &typ, pos, vel, theta, omega)| FruitState {
+                entity,
+                typ,
+                pos: **pos,
+                vel: **vel,
+                theta: **theta,
+                omega: **omega,
+            })
+            .collect()
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fruit::world::{BOTTOM, LEFT, RIGHT};
+
+    const DT: Duration = Duration::from_micros(15_625);
+
+    fn play(seed: u64) -> Vec<FruitState> {
+        let mut sim = FruitSim::new(seed, DT);
+        for i in 0..20 {
+            sim.drop_at(LEFT + (RIGHT - LEFT) * (i % 7) as f32 / 7.);
+            sim.run(30);
+        }
+        sim.fruits()
+    }
+
+    #[test]
+    fn test_drop_spawns_fruit() {
+        let mut sim = FruitSim::new(0, DT);
+        let typ = sim.player();
+        sim.drop_at(0.);
+        assert!(sim.fruits().is_empty());
+        sim.step();
+        assert_eq!(sim.drops(), 1);
+        let fruits = sim.fruits();
+        assert_eq!(fruits.len(), 1);
+        assert_eq!(fruits[0].typ, typ);
+        assert_eq!(fruits[0].pos.x, 0.);
+    }
+
+    #[test]
+    fn test_player_takes_front_of_queue() {
+        let mut sim = FruitSim::new(5, DT);
+        sim.step();
+        for _ in 0..10 {
+            let queue = sim.queue();
+            assert_eq!(queue.len(), 3);
+            sim.drop_at(0.);
+            sim.step();
+            assert_eq!(sim.player(), queue[0]);
+            assert_eq!(sim.queue()[..2], queue[1..]);
+        }
+    }
+
+    #[test]
+    fn test_every_game_starts_from_the_queue() {
+        let catalogue = FruitCatalogue::default();
+        let mut rng = FruitRng::from_seed(42);
+        let mut queue = NextFruitQueue::default();
+        let first = queue.next(&mut rng, &catalogue);
+        let mut sim = FruitSim::new(42, DT);
+        assert_eq!(sim.player(), first);
+        assert_eq!(sim.queue(), queue.iter().collect::<Vec<_>>());
+
+        rng.next_game();
+        queue.clear();
+        let second = queue.next(&mut rng, &catalogue);
+        sim.reset();
+        sim.step();
+        assert_eq!(sim.player(), second);
+    }
+
+    #[test]
+    fn test_hold_once_per_drop() {
+        let mut sim = FruitSim::new(3, DT);
+        sim.step();
+        let first = sim.player();
+        let next = sim.queue()[0];
+        sim.hold();
+        sim.step();
+        assert_eq!(sim.held(), Some(first));
+        assert_eq!(sim.player(), next);
+
+        sim.hold();
+        sim.step();
+        assert_eq!(sim.held(), Some(first));
+        assert_eq!(sim.player(), next);
+
+        sim.drop_at(0.);
+        sim.step();
+        let after_drop = sim.player();
+        sim.hold();
+        sim.step();
+        assert_eq!(sim.held(), Some(after_drop));
+        assert_eq!(sim.player(), first);
+    }
+
+    #[test]
+    fn test_same_seed_same_game() {
+        let a = play(1234);
+        assert!(!a.is_empty());
+        assert_eq!(a, play(1234));
+        for fruit in a {
+            assert!(fruit.pos.y > BOTTOM);
+        }
+    }
+}
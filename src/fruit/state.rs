@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+
+use crate::fruit::collision::Collider;
+use crate::fruit::highscore::HighScores;
+use crate::fruit::input::Player;
+use crate::fruit::replay::{Playback, Recorder, ReplayAction, ReplayEvent};
+use crate::fruit::reset::{ResetEvent, reset};
+use crate::fruit::score::Score;
+use crate::fruit::sim::FixedTick;
+use crate::fruit::typ::FruitCatalogue;
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameState {
+    #[default]
+    Menu,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/// Every state with a container on screen. The container, player and HUD are
+/// spawned on entering it and torn down when going back to the menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InGame;
+
+impl ComputedStates for InGame {
+    type SourceStates = GameState;
+
+    fn compute(sources: GameState) -> Option<Self> {
+        match sources {
+            GameState::Menu => None,
+            GameState::Playing | GameState::Paused | GameState::GameOver => Some(InGame),
+        }
+    }
+}
+
+pub fn load_menu_screen(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        StateScoped(GameState::Menu),
+        Text2d::new("FRUIT\n\nEnter to play\nUp to hold a fruit\nEsc to pause"),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_xyz(0., 0., 200.),
+    ));
+}
+
+pub fn load_pause_screen(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    time.pause();
+    commands.spawn((
+        StateScoped(GameState::Paused),
+        Text2d::new("PAUSED\n\nEsc to resume\nQ to quit to menu"),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_xyz(0., 0., 200.),
+    ));
+}
+
+pub fn unload_pause_screen(
+    mut time: ResMut<Time<Virtual>>,
+) {
+    time.unpause();
+}
+
+pub fn load_game_over_screen(
+    mut commands: Commands,
+    score: Res<Score>,
+    high_scores: Res<HighScores>,
+    catalogue: Res<FruitCatalogue>,
+) {
+    let name = |typ: Option<_>| typ.map(|typ| catalogue[typ].name.clone()).unwrap_or_else(|| "-".to_string());
+    let table: Vec<String> = high_scores.entries.iter().enumerate()
+        .map(|(i, entry)| format!(
+            "{}{:>2}. {:>6}  {:<10}  {:>3} drops  seed {}  {}",
+            if high_scores.latest == Some(i) { "> " } else { "  " },
+            i + 1, entry.points, name(entry.biggest), entry.drops, entry.seed, entry.date,
+        ))
+        .collect();
+    commands.spawn((
+        StateScoped(GameState::GameOver),
+        Text2d::new(format!(
+            "GAME OVER\nscore {}\nbest combo x{}\nbiggest {}\n\n{}\n\nEnter to play again\nEsc for the menu",
+            score.points, score.best_combo, name(score.biggest), table.join("\n"),
+        )),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_xyz(0., 0., 200.),
+    ));
+}
+
+/// Clear away the fruit and the player when leaving for the menu, and reset
+/// the game there and then. A [`ResetEvent`] would wait for the simulation,
+/// which doesn't run in the menu, so the reset is recorded here instead to
+/// keep a replay in step with the game.
+pub fn unload_game(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<Collider>, With<Player>)>>,
+    tick: Res<FixedTick>,
+    recorder: Option<ResMut<Recorder>>,
+) {
+    for entity in query {
+        commands.entity(entity).despawn();
+    }
+    commands.run_system_cached(reset);
+    if let Some(mut recorder) = recorder {
+        recorder.replay.events.push(ReplayEvent { tick: **tick, action: ReplayAction::Reset });
+    }
+}
+
+pub fn end_game(
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    next_state.set(GameState::GameOver);
+}
+
+pub fn menu_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        next_state.set(GameState::Playing);
+    }
+}
+
+pub fn pause_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    match **state {
+        GameState::Playing if keyboard_input.just_pressed(KeyCode::Escape) => {
+            next_state.set(GameState::Paused);
+        }
+        GameState::Paused if keyboard_input.just_pressed(KeyCode::Escape) => {
+            next_state.set(GameState::Playing);
+        }
+        GameState::Paused if keyboard_input.just_pressed(KeyCode::KeyQ) => {
+            next_state.set(GameState::Menu);
+        }
+        _ => {}
+    }
+}
+
+pub fn game_over_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut reset_writer: EventWriter<ResetEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        reset_writer.write(ResetEvent);
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+/// A replay restarts on its own; its next action is the reset that was
+/// recorded when the player pressed Enter.
+pub fn continue_replay(
+    playback: Res<Playback>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !playback.is_finished() {
+        next_state.set(GameState::Playing);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+    use crate::fruit::input::load_player;
+    use crate::fruit::sim::{FruitSim, FruitSimSet};
+
+    fn go_to(sim: &mut FruitSim, state: GameState) {
+        sim.app_mut().world_mut().resource_mut::<NextState<GameState>>().set(state);
+        sim.step();
+    }
+
+    #[test]
+    fn test_menu_starts_a_new_game() {
+        let mut sim = FruitSim::new(11, Duration::from_micros(15_625));
+        // Like the game, spawn the player on the way in rather than at startup.
+        let world = sim.app_mut().world_mut();
+        let players: Vec<Entity> = world.query_filtered::<Entity, With<Player>>().iter(world).collect();
+        for player in players {
+            world.despawn(player);
+        }
+        sim.app_mut()
+            .add_plugins(StatesPlugin)
+            .insert_state(GameState::Menu)
+            .add_computed_state::<InGame>()
+            .configure_sets(FixedUpdate, FruitSimSet.run_if(in_state(GameState::Playing)))
+            .add_systems(OnEnter(InGame), load_player)
+            .add_systems(OnExit(InGame), unload_game);
+
+        go_to(&mut sim, GameState::Playing);
+        let seed = sim.seed();
+        for x in [-100., 100.] {
+            sim.drop_at(x);
+            sim.run(20);
+        }
+        sim.app_mut().world_mut().resource_mut::<Score>().points = 40;
+        assert_eq!(sim.drops(), 2);
+
+        go_to(&mut sim, GameState::Menu);
+        sim.run(10);
+        go_to(&mut sim, GameState::Playing);
+        assert_eq!(sim.score(), Score::default());
+        assert_eq!(sim.drops(), 0);
+        assert!(sim.fruits().is_empty());
+        assert_ne!(sim.seed(), seed);
+
+        sim.drop_at(0.);
+        sim.step();
+        assert_eq!(sim.drops(), 1);
+    }
+}
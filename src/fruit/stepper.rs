@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::fruit::Fruit;
+use crate::fruit::collision::{Collider, Sleep};
+use crate::fruit::danger::Danger;
+use crate::fruit::pva::{Acceleration, Position, PreviousPosition, Velocity};
+use crate::fruit::replay::{Playback, Recorder};
+use crate::fruit::sim::FixedTick;
+use crate::fruit::state::InGame;
+use crate::fruit::toa::{Alpha, Omega, PreviousTheta, Theta};
+use crate::fruit::typ::FruitType;
+use crate::fruit::world::HudAnchor;
+
+/// How many fixed ticks can be rewound.
+const HISTORY: usize = 600;
+
+/// Freezes the simulation so it can be stepped one fixed tick at a time. F5
+/// freezes and thaws, F6 steps forward and F7 rewinds, except while recording
+/// or replaying, where going back would lose track of the replay's ticks.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Default)]
+pub struct Stepper {
+    pub frozen: bool,
+    /// Ticks still to run before freezing again.
+    steps: u32,
+}
+
+/// The motion of one fruit at the end of a fixed tick, how long it has sat
+/// above the danger line and whether it has gone to sleep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FruitSnapshot {
+    pub typ: FruitType,
+    pub pos: Position,
+    pub pre: PreviousPosition,
+    pub vel: Velocity,
+    pub acc: Acceleration,
+    pub theta: Theta,
+    pub omega: Omega,
+    pub alpha: Alpha,
+    pub danger: Danger,
+    pub sleep: Sleep,
+}
+
+/// Every fruit in the container at the end of fixed tick `tick`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub fruits: Vec<FruitSnapshot>,
+}
+
+/// The last [`HISTORY`] snapshots, oldest first. The last one is the board as
+/// it is now.
+#[derive(Debug, Resource, Clone, PartialEq, Default, Deref, DerefMut)]
+pub struct History(pub VecDeque<Snapshot>);
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct StepperDisplay;
+
+impl Snapshot {
+    pub fn take(world: &mut World) -> Self {
+        let tick = **world.resource::<FixedTick>();
+        let fruits = world
+            .query_filtered::<(&FruitType, &Position, &PreviousPosition, &Velocity, &Acceleration, &Theta, &Omega, &Alpha, &Danger, &Sleep), With<Collider>>()
+            .iter(world)
+            .map(|(&typ, &pos, &pre, &vel, &acc, &theta, &omega, &alpha, &danger, &sleep)| FruitSnapshot { typ, pos, pre, vel, acc, theta, omega, alpha, danger, sleep })
+            .collect();
+        Self { tick, fruits }
+    }
+
+    /// Swap every fruit in the container for the ones in this snapshot. Only
+    /// the fruit go back in time; the score, the queue and the tick count don't.
+    pub fn restore(&self, world: &mut World) {
+        let entities: Vec<Entity> = world.query_filtered::<Entity, With<Collider>>().iter(world).collect();
+        for entity in entities {
+            world.despawn(entity);
+        }
+        for fruit in &self.fruits {
+            world.spawn((
+                Fruit {
+                    typ: fruit.typ,
+                    pos: fruit.pos,
+                    pre: fruit.pre,
+                    vel: fruit.vel,
+                    acc: fruit.acc,
+                    theta: fruit.theta,
+                    pre_theta: PreviousTheta(*fruit.theta),
+                    omega: fruit.omega,
+                    alpha: fruit.alpha,
+                },
+                fruit.danger,
+                fruit.sleep,
+                Transform::from_translation(fruit.pos.extend(0.)),
+                Collider,
+            ));
+        }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, snapshot: Snapshot) {
+        if self.len() == HISTORY {
+            self.pop_front();
+        }
+        self.push_back(snapshot);
+    }
+}
+
+/// Run condition for the simulation: always while thawed, and only for the
+/// ticks asked for while frozen.
+pub fn sim_unfrozen(stepper: Res<Stepper>) -> bool {
+    !stepper.frozen || stepper.steps > 0
+}
+
+pub fn reset_stepper(
+    mut commands: Commands,
+) {
+    commands.insert_resource(Stepper::default());
+    commands.insert_resource(History::default());
+}
+
+pub fn stepper_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut stepper: ResMut<Stepper>,
+    recorder: Option<Res<Recorder>>,
+    playback: Option<Res<Playback>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        stepper.frozen = !stepper.frozen;
+        stepper.steps = 0;
+    }
+    if !stepper.frozen {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        stepper.steps += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::F7) && recorder.is_none() && playback.is_none() {
+        commands.queue(rewind);
+    }
+}
+
+/// Go back one tick, if there is one to go back to.
+pub fn rewind(world: &mut World) {
+    let mut history = world.resource_mut::<History>();
+    if history.len() < 2 {
+        return;
+    }
+    history.pop_back();
+    let snapshot = history.back().cloned().unwrap();
+    snapshot.restore(world);
+}
+
+pub fn record_snapshot(world: &mut World) {
+    let snapshot = Snapshot::take(world);
+    world.resource_mut::<History>().record(snapshot);
+}
+
+pub fn use_step(
+    mut stepper: ResMut<Stepper>,
+) {
+    if stepper.frozen {
+        stepper.steps = stepper.steps.saturating_sub(1);
+    }
+}
+
+pub fn load_stepper_display(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        StepperDisplay,
+        StateScoped(InGame),
+        Text2d::new(""),
+        HudAnchor::new(Vec2::new(0.5, 0.), Vec2::new(0., -70.)),
+    ));
+}
+
+pub fn update_stepper_display(
+    stepper: Res<Stepper>,
+    history: Res<History>,
+    stepper_display: Single<&mut Text2d, With<StepperDisplay>>,
+) {
+    if !stepper.is_changed() && !history.is_changed() {
+        return;
+    }
+    stepper_display.into_inner().0 = match history.back() {
+        Some(snapshot) if stepper.frozen => format!(
+            "frozen at tick {}, {} to rewind  F6 step  F7 rewind  F5 resume",
+            snapshot.tick, history.len() - 1,
+        ),
+        _ => String::new(),
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::fruit::sim::FruitSim;
+
+    #[test]
+    fn test_restore_snapshot() {
+        let mut sim = FruitSim::new(0, Duration::from_micros(15_625));
+        for x in [-100., 0., 100.] {
+            sim.drop_at(x);
+            sim.run(20);
+        }
+        // Long enough for the pile to go to sleep.
+        sim.run(300);
+        // As if the top fruit had been poking over the danger line for a while.
+        let world = sim.app_mut().world_mut();
+        let mut dangers = world.query_filtered::<&mut Danger, With<Collider>>();
+        **dangers.iter_mut(world).last().unwrap() = 1.5;
+        let snapshot = Snapshot::take(world);
+        assert!(snapshot.fruits.iter().any(|fruit| fruit.sleep.asleep));
+        let before = sim.fruits();
+        sim.drop_at(50.);
+        sim.run(40);
+
+        snapshot.restore(sim.app_mut().world_mut());
+        let after = sim.fruits();
+        assert_eq!(after.len(), before.len());
+        for (a, b) in before.iter().zip(&after) {
+            assert_eq!((a.typ, a.pos, a.vel, a.theta, a.omega), (b.typ, b.pos, b.vel, b.theta, b.omega));
+        }
+        assert_eq!(Snapshot::take(sim.app_mut().world_mut()).fruits, snapshot.fruits);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = History::default();
+        for tick in 0..HISTORY as u64 + 10 {
+            history.record(Snapshot { tick, fruits: vec![] });
+        }
+        assert_eq!(history.len(), HISTORY);
+        assert_eq!(history.front().unwrap().tick, 10);
+    }
+}
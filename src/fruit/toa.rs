@@ -3,6 +3,9 @@ use bevy::prelude::*;
 #[derive(Debug, Component, Clone, Copy, PartialEq, Default, Deref, DerefMut)]
 pub struct Theta(pub f32);
 
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default, Deref, DerefMut)]
+pub struct PreviousTheta(pub f32);
+
 #[derive(Debug, Component, Clone, Copy, PartialEq, Default, Deref, DerefMut)]
 pub struct Omega(pub f32);
 
@@ -12,11 +15,12 @@ pub struct Alpha(pub f32);
 
 pub fn apply_omega(
     time: Res<Time>,
-    mut query: Query<(&mut Theta, &Omega, &Alpha)>,
+    query: Query<(&mut Theta, &mut PreviousTheta, &Omega, &Alpha)>,
 ) {
     let dt = time.delta_secs();
     let dt2 = 0.5 * dt * dt;
-    for (mut theta, omega, alpha) in query {
+    for (mut theta, mut pre, omega, alpha) in query {
+        pre.0 = theta.0;
         **theta += **omega * dt + **alpha * dt2;
     }
 }
@@ -1,82 +1,166 @@
+use std::io;
+use std::ops::Index;
+
+use bevy::asset::{AssetLoader, LoadContext, io::Reader};
 use bevy::prelude::*;
-use bevy::color::palettes::basic::*;
-use rand::seq::IndexedRandom;
-
-const RADIUS_BLUEBERRY: f32 = 10.0;
-const DENSITY: f32 = 1e2;
-
-#[derive(Component, Clone, Copy, Default, Debug, PartialEq, PartialOrd, Eq, Ord)]
-pub enum FruitType {
-    #[default]
-    Blueberry,
-    Cherry,
-    Apricot,
-    Plum,
-    Orange,
-    Apple,
-    Grapefruit,
-    Honeydew,
-    Basketball,
-    Watermelon,
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// A tier of the fruit ladder, as an index into the [`FruitCatalogue`].
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Deref, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FruitType(pub usize);
+
+/// One tier of the fruit ladder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FruitTier {
+    pub name: String,
+    pub radius: f32,
+    pub density: f32,
+    /// sRGB hex, like `"#ff0000"`.
+    pub color: String,
+    /// Image drawn in place of the plain circle, relative to the asset folder.
+    #[serde(default)]
+    pub sprite: Option<String>,
+    /// Points for making a fruit of this tier by merging two smaller ones.
+    pub points: u64,
 }
 
+/// Every fruit tier, smallest first, loaded from `assets/fruits.ron`. The sim
+/// starts from a copy built into the binary; the game swaps in the asset once
+/// it loads and again whenever the file changes.
+#[derive(Debug, Asset, Resource, TypePath, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FruitCatalogue {
+    /// The biggest tier the player is ever handed.
+    pub largest_drop: FruitType,
+    pub tiers: Vec<FruitTier>,
+}
 
-impl FruitType {
-    const ALL: [FruitType; 10] = [
-        FruitType::Blueberry,
-        FruitType::Cherry,
-        FruitType::Apricot,
-        FruitType::Plum,
-        FruitType::Orange,
-        FruitType::Apple,
-        FruitType::Grapefruit,
-        FruitType::Honeydew,
-        FruitType::Basketball,
-        FruitType::Watermelon,
-    ];
+/// The catalogue asset the game is using.
+#[derive(Debug, Resource, Clone, Deref)]
+pub struct FruitCatalogueHandle(pub Handle<FruitCatalogue>);
 
-    pub fn rand_up_to(upper: Self) -> Self {
-        let mut rng = rand::rng();
-        let choices: Vec<FruitType> = FruitType::ALL.into_iter().take_while(|typ| typ <= &upper).collect();
-        *choices.choose(&mut rng).unwrap()
-    }
+#[derive(Default)]
+pub struct FruitCatalogueLoader;
 
-    pub fn next(&self) -> Option<FruitType> {
-        let i = Self::ALL.binary_search(self).unwrap();
-        Self::ALL.as_slice().get(i + 1).copied()
+impl FruitTier {
+    pub fn to_circle(&self) -> Circle {
+        Circle::new(self.radius)
     }
 
-    pub fn to_circle(&self) -> Circle {
-        Circle::new(self.radius())
+    pub fn mass(&self) -> f32 {
+        self.radius * self.radius * self.density
     }
 
-    pub fn radius(&self) -> f32 {
-        let mut r = RADIUS_BLUEBERRY;
-        let i = Self::ALL.binary_search(self).unwrap();
-        for _ in 0..i {
-            r *= 1.41421356237;
-        }
-        r
+    /// Moment of inertia of a solid disc.
+    pub fn inertia(&self) -> f32 {
+        0.5 * self.mass() * self.radius * self.radius
     }
 
     pub fn color(&self) -> Color {
-        match self {
-            FruitType::Blueberry => Color::from(BLUE),
-            FruitType::Cherry => Color::from(RED),
-            FruitType::Apricot => Color::from(OLIVE),
-            FruitType::Plum => Color::from(PURPLE),
-            FruitType::Orange => Color::from(AQUA),
-            FruitType::Apple => Color::from(LIME),
-            FruitType::Grapefruit => Color::from(GRAY),
-            FruitType::Honeydew => Color::from(SILVER),
-            FruitType::Basketball => Color::from(NAVY),
-            FruitType::Watermelon => Color::from(GREEN),
+        Srgba::hex(&self.color).map(Color::from).unwrap_or(Color::WHITE)
+    }
+}
+
+impl Default for FruitCatalogue {
+    fn default() -> Self {
+        FruitCatalogue::parse(include_str!("../../assets/fruits.ron")).expect("built-in fruit catalogue")
+    }
+}
+
+impl FruitCatalogue {
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let catalogue: Self = ron::from_str(text).map_err(io::Error::other)?;
+        if catalogue.tiers.is_empty() {
+            return Err(io::Error::other("fruit catalogue has no tiers"));
         }
+        for tier in &catalogue.tiers {
+            if !(tier.radius > 0. && tier.density > 0.) {
+                return Err(io::Error::other(format!("{} needs a positive radius and density", tier.name)));
+            }
+            if Srgba::hex(&tier.color).is_err() {
+                return Err(io::Error::other(format!("{} has a bad color {:?}", tier.name, tier.color)));
+            }
+        }
+        Ok(catalogue)
     }
 
-    pub fn mass(&self) -> f32 {
-        let r = self.radius();
-        r * r * DENSITY
+    pub fn len(&self) -> usize {
+        self.tiers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    pub fn largest(&self) -> FruitType {
+        FruitType(self.tiers.len() - 1)
+    }
+
+    /// The tier two fruit of type `typ` merge into, if it isn't the last.
+    pub fn next(&self, typ: FruitType) -> Option<FruitType> {
+        (*typ + 1 < self.tiers.len()).then_some(FruitType(*typ + 1))
+    }
+
+    /// A random tier up to and including `upper`.
+    pub fn rand_up_to<R: Rng + ?Sized>(&self, upper: FruitType, rng: &mut R) -> FruitType {
+        FruitType(rng.random_range(0..=*upper.min(self.largest())))
+    }
+}
+
+/// Fruit left over from a longer catalogue before a reload are drawn and
+/// simulated as the last tier.
+impl Index<FruitType> for FruitCatalogue {
+    type Output = FruitTier;
+
+    fn index(&self, typ: FruitType) -> &FruitTier {
+        &self.tiers[*typ.min(self.largest())]
+    }
+}
+
+impl AssetLoader for FruitCatalogueLoader {
+    type Asset = FruitCatalogue;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<FruitCatalogue, io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let text = String::from_utf8(bytes).map_err(io::Error::other)?;
+        FruitCatalogue::parse(&text)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["fruits.ron"]
+    }
+}
+
+pub fn load_catalogue(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(FruitCatalogueHandle(asset_server.load("fruits.ron")));
+}
+
+/// Swap in the catalogue asset whenever it finishes loading or is edited.
+pub fn update_catalogue(
+    mut events: EventReader<AssetEvent<FruitCatalogue>>,
+    handle: Res<FruitCatalogueHandle>,
+    assets: Res<Assets<FruitCatalogue>>,
+    mut catalogue: ResMut<FruitCatalogue>,
+) {
+    for event in events.read() {
+        if (event.is_loaded_with_dependencies(handle.id()) || event.is_modified(handle.id()))
+            && let Some(loaded) = assets.get(handle.id())
+        {
+            info!("loaded {} fruit tiers", loaded.len());
+            catalogue.set_if_neq(loaded.clone());
+        }
     }
 }
 
@@ -86,15 +170,22 @@ mod test {
 
     #[test]
     fn test_next() {
-        assert_eq!(FruitType::Blueberry.next(), Some(FruitType::Cherry));
-        assert_eq!(FruitType::Cherry.next(), Some(FruitType::Apricot));
-        assert_eq!(FruitType::Apricot.next(), Some(FruitType::Plum));
-        assert_eq!(FruitType::Plum.next(), Some(FruitType::Orange));
-        assert_eq!(FruitType::Orange.next(), Some(FruitType::Apple));
-        assert_eq!(FruitType::Apple.next(), Some(FruitType::Grapefruit));
-        assert_eq!(FruitType::Grapefruit.next(), Some(FruitType::Honeydew));
-        assert_eq!(FruitType::Honeydew.next(), Some(FruitType::Basketball));
-        assert_eq!(FruitType::Basketball.next(), Some(FruitType::Watermelon));
-        assert_eq!(FruitType::Watermelon.next(), None);
+        let catalogue = FruitCatalogue::default();
+        assert_eq!(catalogue.len(), 10);
+        for i in 0..9 {
+            assert_eq!(catalogue.next(FruitType(i)), Some(FruitType(i + 1)));
+            assert!(catalogue[FruitType(i + 1)].radius > catalogue[FruitType(i)].radius);
+        }
+        assert_eq!(catalogue.next(FruitType(9)), None);
+        assert_eq!(catalogue[FruitType(0)].name, "Blueberry");
+        assert_eq!(catalogue[FruitType(12)].name, "Watermelon");
+    }
+
+    #[test]
+    fn test_rejects_bad_catalogue() {
+        assert!(FruitCatalogue::parse("(largest_drop: 0, tiers: [])").is_err());
+        assert!(FruitCatalogue::parse(r##"(largest_drop: 0, tiers: [
+            (name: "Grape", radius: 5.0, density: 1.0, color: "purple", points: 1),
+        ])"##).is_err());
     }
 }
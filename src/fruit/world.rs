@@ -1,8 +1,13 @@
 use bevy::prelude::*;
 use bevy::color::palettes::basic::*;
 
+use crate::fruit::physics::PhysicsConfig;
+use crate::fruit::state::InGame;
+
+// The container of the default physics preset. The walls fruit actually hit,
+// and the HUD around them, come from the `PhysicsConfig`; only the top is fixed.
 pub const RIGHT: f32 = 300.;
-pub const LEFT: f32 = RIGHT * -1.0;
+pub const LEFT: f32 = -RIGHT;
 pub const TOP: f32 = 300.;
 pub const BOTTOM: f32 = -300.;
 pub const THICKNESS: f32 = 2.;
@@ -10,38 +15,88 @@ pub const THICKNESS: f32 = 2.;
 #[derive(Component, Default)]
 pub struct Wall;
 
+/// Keeps a HUD element at `offset` from a point on the container. `edge` is how
+/// far that point is from the left wall to the right and from the bottom to the
+/// top, so `(0., 0.)` is the bottom left corner.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct HudAnchor {
+    pub edge: Vec2,
+    pub offset: Vec2,
+}
+
+impl HudAnchor {
+    pub fn new(edge: Vec2, offset: Vec2) -> Self {
+        Self { edge, offset }
+    }
+
+    pub fn place(&self, physics: &PhysicsConfig) -> Vec2 {
+        let min = Vec2::new(physics.left, physics.bottom);
+        let max = Vec2::new(physics.right, TOP);
+        min + (max - min) * self.edge + self.offset
+    }
+}
+
+pub fn load_camera(
+    mut commands: Commands,
+) {
+    commands.spawn(Camera2d);
+}
+
+/// Draw the walls of the container, again whenever the [`PhysicsConfig`]
+/// moves them.
 pub fn load_container(
     mut commands: Commands,
+    physics: Res<PhysicsConfig>,
+    walls: Query<Entity, With<Wall>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    commands.spawn(Camera2d);
-
+    for wall in walls {
+        commands.entity(wall).despawn();
+    }
+    let PhysicsConfig { left, right, bottom, .. } = *physics;
     let layer = 100.;
     let color = Color::from(WHITE);
 
     commands.spawn((
-        Mesh2d(meshes.add(Rectangle::new(THICKNESS, TOP - BOTTOM))),
+        Mesh2d(meshes.add(Rectangle::new(THICKNESS, TOP - bottom))),
         MeshMaterial2d(materials.add(color)),
-        Transform::from_xyz(RIGHT, 0., layer),
+        Transform::from_xyz(right, (TOP + bottom) / 2., layer),
         Wall,
+        StateScoped(InGame),
     ));
     commands.spawn((
-        Mesh2d(meshes.add(Rectangle::new(THICKNESS, TOP - BOTTOM))),
+        Mesh2d(meshes.add(Rectangle::new(THICKNESS, TOP - bottom))),
         MeshMaterial2d(materials.add(color)),
-        Transform::from_xyz(LEFT, 0., layer),
+        Transform::from_xyz(left, (TOP + bottom) / 2., layer),
         Wall,
+        StateScoped(InGame),
     ));
     commands.spawn((
-        Mesh2d(meshes.add(Rectangle::new(RIGHT - LEFT, THICKNESS))),
+        Mesh2d(meshes.add(Rectangle::new(right - left, THICKNESS))),
         MeshMaterial2d(materials.add(color)),
-        Transform::from_xyz(0., BOTTOM, layer),
+        Transform::from_xyz((left + right) / 2., bottom, layer),
         Wall,
+        StateScoped(InGame),
     ));
     commands.spawn((
-        Mesh2d(meshes.add(Rectangle::new(RIGHT - LEFT, THICKNESS))),
+        Mesh2d(meshes.add(Rectangle::new(right - left, THICKNESS))),
         MeshMaterial2d(materials.add(color)),
-        Transform::from_xyz(0., TOP, layer),
+        Transform::from_xyz((left + right) / 2., TOP, layer),
         Wall,
+        StateScoped(InGame),
     ));
 }
+
+/// Move new [`HudAnchor`]s into place, and all of them whenever the
+/// [`PhysicsConfig`] moves the walls.
+pub fn place_hud(
+    physics: Res<PhysicsConfig>,
+    query: Query<(Ref<HudAnchor>, &mut Transform)>,
+) {
+    for (anchor, mut transform) in query {
+        if physics.is_changed() || anchor.is_added() {
+            transform.translation = anchor.place(&physics).extend(transform.translation.z);
+        }
+    }
+}
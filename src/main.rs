@@ -1,11 +1,59 @@
+use std::str::FromStr;
+
 use bevy::prelude::*;
 
 use drive::fruit::FruitGame;
+use drive::race::RaceGame;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugins(FruitGame)
-        .run();
+    let mut game = FruitGame::default();
+    let mut race = RaceGame::default();
+    let mut racing = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => game.seed = Some(parse_arg(&arg, args.next())),
+            "--record" => game.record = args.next().map(Into::into),
+            "--replay" => game.replay = args.next().map(Into::into),
+            "--preview" => game.preview = Some(parse_arg(&arg, args.next())),
+            "--high-scores" => game.high_scores = args.next().map(Into::into),
+            "--physics" => game.physics = args.next(),
+            "--board" => game.board = args.next().map(Into::into),
+            "--race" => racing = true,
+            "--track" => race.track = args.next(),
+            "--ghosts" => race.ghosts = args.next().map(Into::into),
+            "--opponents" => race.opponents = Some(parse_arg(&arg, args.next())),
+            _ => eprintln!("ignoring unknown argument {arg}"),
+        }
+    }
+    // A seed in the environment stands in for one on the command line, so a
+    // shell or launcher can pin every game to it.
+    if game.seed.is_none() && let Ok(seed) = std::env::var("FRUIT_SEED") {
+        game.seed = Some(parse_arg("FRUIT_SEED", Some(seed)));
+    }
+    if game.board.is_some() && game.record.is_some() {
+        eprintln!("--board can't be used with --record, as loading a board isn't recorded");
+        std::process::exit(2);
+    }
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    if racing {
+        app.add_plugins(race);
+    } else {
+        app.add_plugins(game);
+    }
+    app.run();
 }
 
+/// The value given for `name`, or exit if it's missing or can't be read.
+fn parse_arg<T: FromStr>(name: &str, value: Option<String>) -> T {
+    let Some(value) = value else {
+        eprintln!("{name} needs a value");
+        std::process::exit(2);
+    };
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("{name} can't be {value:?}");
+        std::process::exit(2);
+    })
+}
@@ -0,0 +1,315 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::fruit::pva::{Position, Velocity};
+use crate::fruit::toa::Theta;
+use crate::race::car::{CarControls, CarSpec, GRAVITY};
+use crate::race::track::Track;
+
+/// How far inside the edge of the road the racing line keeps the middle of
+/// the car, beyond half its width.
+const LINE_MARGIN: f32 = 0.8;
+/// Sideways grip the racing line plans to use, as a share of what the tyres
+/// can take.
+const CORNER_GRIP: f32 = 0.8;
+/// Braking the racing line plans for, as a share of what the tyres can take.
+const BRAKE_GRIP: f32 = 0.6;
+/// How many centreline points either side the curvature is measured across.
+const CURVATURE_SPAN: usize = 3;
+/// The shortest distance ahead, in metres, an AI driver aims for, and how much
+/// further per metre per second of speed.
+const LOOKAHEAD: f32 = 5.;
+const LOOKAHEAD_PER_SPEED: f32 = 0.5;
+/// Throttle or brake per metre per second off the target speed.
+const SPEED_GAIN: f32 = 0.5;
+/// How often, in metres along the track, an AI driver changes its mind about
+/// where it brakes and where it drifts to.
+const ERROR_SPAN: f32 = 80.;
+/// How far ahead, in metres, an AI driver looks out for other cars.
+const TRAFFIC_RANGE: f32 = 80.;
+/// Room, in metres, an AI driver leaves beside and behind other cars.
+const CLEARANCE: f32 = 1.2;
+/// How fast, in metres per second, an AI driver moves across the road.
+const LANE_SPEED: f32 = 3.;
+/// Slowest an AI driver goes, in metres per second, while pulling out round a
+/// car in front.
+const CRAWL_SPEED: f32 = 2.;
+
+/// The fastest way round the track for a car with the default [`CarSpec`]:
+/// where to be across the road and how fast to go at every centreline point.
+#[derive(Debug, Resource, Clone, PartialEq, Default)]
+pub struct RacingLine {
+    /// Distance left of the centreline at each of the track's points.
+    pub offsets: Vec<f32>,
+    pub points: Vec<Vec2>,
+    /// Top speed at each point, slowing in time for the corners after it.
+    pub speeds: Vec<f32>,
+}
+
+/// How well an AI driver drives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skill {
+    /// Share of the racing line's speed the driver aims for.
+    pub pace: f32,
+    /// Furthest, in metres, the driver misjudges a braking point either way.
+    pub braking_error: f32,
+    /// Furthest, in metres, the driver wanders off the racing line.
+    pub line_error: f32,
+}
+
+/// Drives a car round the racing line in place of a player.
+#[derive(Debug, Component, Clone)]
+pub struct AiDriver {
+    pub skill: Skill,
+    /// What `rng` was seeded with, so a reset can make the same mistakes again.
+    pub seed: u64,
+    rng: ChaCha12Rng,
+    /// Which stretch of `ERROR_SPAN` metres the current errors are for.
+    stretch: Option<u32>,
+    brake_error: f32,
+    wander: f32,
+    /// Distance left of the racing line the driver is aiming for.
+    lane: f32,
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Skill::from_level(0.8)
+    }
+}
+
+impl Skill {
+    /// A driver from hopeless at 0 to flawless at 1.
+    pub fn from_level(level: f32) -> Self {
+        let level = level.clamp(0., 1.);
+        Self {
+            pace: 0.75 + 0.25 * level,
+            braking_error: 20. * (1. - level),
+            line_error: 2. * (1. - level),
+        }
+    }
+}
+
+impl AiDriver {
+    /// A driver with its own stream of mistakes, drawn from `seed`.
+    pub fn new(skill: Skill, seed: u64) -> Self {
+        Self {
+            skill,
+            seed,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            stretch: None,
+            brake_error: 0.,
+            wander: 0.,
+            lane: 0.,
+        }
+    }
+}
+
+impl RacingLine {
+    /// Pull the line as straight as the road allows, then work out how fast a
+    /// car with `spec` can take each bend and where it has to brake for it.
+    pub fn new(track: &Track, spec: &CarSpec) -> Self {
+        let n = track.points.len();
+        let normals: Vec<_> = (0..n).map(|i| track.normal(i)).collect();
+        let room: Vec<_> = track.widths.iter().map(|width| (width / 2. - spec.width / 2. - LINE_MARGIN).max(0.)).collect();
+        let mut offsets = vec![0.; n];
+        // Each point slides across the road toward the middle of its
+        // neighbours, first far apart to get the overall shape and then close
+        // together to smooth it.
+        for stride in [16, 8, 4, 2, 1] {
+            for _ in 0..100 {
+                for i in 0..n {
+                    let point = |j: usize| track.points[j] + normals[j] * offsets[j];
+                    let middle = (point((i + n - stride) % n) + point((i + stride) % n)) / 2.;
+                    offsets[i] = (offsets[i] + (middle - point(i)).dot(normals[i])).clamp(-room[i], room[i]);
+                }
+            }
+        }
+        let points: Vec<_> = (0..n).map(|i| track.points[i] + normals[i] * offsets[i]).collect();
+
+        let lateral = spec.grip * GRAVITY * CORNER_GRIP;
+        let braking = planned_braking(spec);
+        let top_speed = (spec.engine / spec.drag).sqrt();
+        let mut speeds: Vec<_> = (0..n)
+            .map(|i| {
+                let (a, b, c) = (points[(i + n - CURVATURE_SPAN) % n], points[i], points[(i + CURVATURE_SPAN) % n]);
+                let curvature = 2. * (b - a).perp_dot(c - b).abs() / (a.distance(b) * b.distance(c) * a.distance(c));
+                (lateral / curvature).sqrt().min(top_speed)
+            })
+            .collect();
+        // Going backwards round twice catches the braking zone before the
+        // start line too.
+        for i in (0..2 * n).rev() {
+            let (here, next) = (i % n, (i + 1) % n);
+            let reachable = (speeds[next] * speeds[next] + 2. * braking * points[here].distance(points[next])).sqrt();
+            speeds[here] = speeds[here].min(reachable);
+        }
+        Self { offsets, points, speeds }
+    }
+
+    /// Where the line is across the road, and how fast to go, `s` along the
+    /// track.
+    pub fn at(&self, track: &Track, s: f32) -> (f32, f32) {
+        let (segment, t) = track.segment_at(s);
+        let next = (segment + 1) % self.offsets.len();
+        (self.offsets[segment].lerp(self.offsets[next], t), self.speeds[segment].lerp(self.speeds[next], t))
+    }
+}
+
+/// Deceleration a driver plans on when braking.
+fn planned_braking(spec: &CarSpec) -> f32 {
+    (spec.brakes / spec.mass).min(spec.grip * GRAVITY) * BRAKE_GRIP
+}
+
+pub fn build_racing_line(
+    track: Res<Track>,
+    mut line: ResMut<RacingLine>,
+) {
+    *line = RacingLine::new(&track, &CarSpec::default());
+}
+
+/// One car as the AI drivers see it.
+struct Traffic {
+    entity: Entity,
+    s: f32,
+    offset: f32,
+    speed: f32,
+}
+
+/// Steer every AI car along the racing line, braking for corners, passing
+/// slower cars where there's room and following them where there isn't.
+pub fn drive_ai(
+    time: Res<Time>,
+    track: Res<Track>,
+    line: Res<RacingLine>,
+    cars: Query<(Entity, &Position, &Velocity, &Theta), With<CarSpec>>,
+    query: Query<(Entity, &CarSpec, &Position, &Velocity, &Theta, &mut CarControls, &mut AiDriver)>,
+) {
+    if line.offsets.len() != track.points.len() {
+        return;
+    }
+    let length = track.length();
+    let traffic: Vec<_> = cars.iter()
+        .map(|(entity, pos, vel, theta)| {
+            let point = track.locate(**pos);
+            let speed = vel.dot(Vec2::from_angle(**theta));
+            Traffic { entity, s: point.s, offset: point.offset, speed }
+        })
+        .collect();
+
+    for (entity, spec, pos, vel, theta, mut controls, mut ai) in query {
+        let me = traffic.iter().find(|car| car.entity == entity).expect("every car is traffic");
+        let heading = Vec2::from_angle(**theta);
+        let speed = vel.dot(heading);
+
+        // A fresh mistake every so often.
+        let stretch = (me.s / ERROR_SPAN) as u32;
+        if ai.stretch != Some(stretch) {
+            ai.stretch = Some(stretch);
+            let skill = ai.skill;
+            ai.brake_error = skill.braking_error * ai.rng.random_range(-1. ..=1.);
+            ai.wander = skill.line_error * ai.rng.random_range(-1. ..=1.);
+        }
+
+        // Aim for the line's speed, then look out for cars ahead, or alongside
+        // and still too close, that are in the way.
+        let (_, line_speed) = line.at(&track, me.s - ai.brake_error + speed.max(0.) * 0.25);
+        let mut target_speed = line_speed * ai.skill.pace;
+        let mut lane = ai.wander;
+        let passing = spec.width + CLEARANCE;
+        let in_the_way: Vec<_> = traffic.iter()
+            .filter(|other| other.entity != entity)
+            .map(|other| ((other.s - me.s + length / 2.).rem_euclid(length) - length / 2., other))
+            .filter(|(gap, _)| *gap > -spec.length && *gap < TRAFFIC_RANGE)
+            .filter(|(gap, other)| {
+                // A little wider than the room left when passing, so a driver
+                // who has pulled out keeps going round.
+                let planned = line.at(&track, me.s + gap).0 + ai.lane;
+                (other.offset - planned).abs() < passing + CLEARANCE
+            })
+            .collect();
+        // Which way round a car there's room to go, the nearer side first.
+        let pass_side = |other: &Traffic| {
+            let (segment, _) = track.segment_at(other.s);
+            let half = track.widths[segment] / 2. - spec.width / 2. - LINE_MARGIN;
+            let sides = if me.offset > other.offset { [1., -1.] } else { [-1., 1.] };
+            sides.into_iter().find(|side| (other.offset + side * passing).abs() <= half)
+        };
+        // Steer round the nearest car in the way that isn't already pulling
+        // away.
+        let nearest = in_the_way.iter()
+            .filter(|(gap, other)| *gap < spec.length || other.speed < speed + CLEARANCE)
+            .min_by(|(a, _), (b, _)| a.abs().total_cmp(&b.abs()));
+        if let Some((_, other)) = nearest
+            && let Some(side) = pass_side(other)
+        {
+            lane = other.offset + side * passing - line.at(&track, other.s).0;
+        }
+        // Follow any car in front at a safe distance until there's a way past
+        // and the car has pulled out far enough to take it.
+        for (gap, other) in in_the_way.iter().filter(|(gap, _)| *gap > 0.) {
+            let side = pass_side(other);
+            let apart = (other.offset - me.offset).abs();
+            if side.is_none() || apart < spec.width + CLEARANCE / 2. {
+                // Hang back a car length further when there's a way past, to
+                // leave room to pull out into it.
+                let pull_out = if side.is_some() { spec.length } else { 0. };
+                let room = gap - spec.length - CLEARANCE - pull_out;
+                // Slow enough to stop short of it if it stopped dead.
+                let mut follow = (other.speed.max(0.).powi(2) + 2. * planned_braking(spec) * room.max(0.)).sqrt()
+                    + room.min(0.) * SPEED_GAIN * 2.;
+                // Creep out from behind a stopped car rather than wait forever.
+                if side.is_some() && (*gap > spec.length + CLEARANCE / 2. || apart > spec.width) {
+                    follow = follow.max(CRAWL_SPEED);
+                }
+                target_speed = target_speed.min(follow.max(0.));
+            }
+        }
+        let step = LANE_SPEED * time.delta_secs();
+        ai.lane += (lane - ai.lane).clamp(-step, step);
+
+        // Pure pursuit of the line a little way ahead.
+        let lookahead = LOOKAHEAD + LOOKAHEAD_PER_SPEED * speed.abs();
+        let s = me.s + lookahead;
+        let (offset, _) = line.at(&track, s);
+        let (segment, _) = track.segment_at(s);
+        // Wandering or passing never means aiming off the road.
+        let room = track.widths[segment] / 2. - spec.width / 2.;
+        let target = track.at(s) + track.normal(segment) * (offset + ai.lane).clamp(-room, room);
+        let to_target = target - **pos;
+        let local = Vec2::new(to_target.dot(heading), to_target.dot(heading.perp()));
+        let curvature = 2. * local.y / local.length_squared();
+        let wheel = (curvature * (spec.front + spec.rear)).atan();
+        let full_lock = spec.max_steer / (1. + vel.length() / spec.steer_falloff);
+
+        let error = target_speed - speed;
+        *controls = CarControls {
+            throttle: (error * SPEED_GAIN).clamp(0., 1.),
+            brake: (-error * SPEED_GAIN).clamp(0., 1.),
+            steer: (wheel / full_lock).clamp(-1., 1.),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_racing_line_cuts_corners() {
+        let track = Track::default();
+        let spec = CarSpec::default();
+        let line = RacingLine::new(&track, &spec);
+        assert!(line.points.iter().all(|&point| track.on_road(point)));
+
+        let speed = |pos: Vec2| line.at(&track, track.locate(pos).s).1;
+        // Flat out down the middle of the front straight, slower round the
+        // bends and through the chicane.
+        let (straight, bend, chicane) = (speed(Vec2::new(-20., -60.)), speed(Vec2::new(160., 0.)), speed(Vec2::new(0., 50.)));
+        assert!(straight > 1.5 * bend && bend > chicane, "{straight} {bend} {chicane}");
+        // Hugging the inside at the apex of the right-hand end.
+        let (offset, _) = line.at(&track, track.locate(Vec2::new(160., 0.)).s);
+        assert!(offset > 2., "{offset}");
+    }
+}
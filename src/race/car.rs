@@ -0,0 +1,201 @@
+use bevy::prelude::*;
+
+use crate::fruit::pva::{Acceleration, Position, PreviousPosition, Velocity};
+use crate::fruit::toa::{Alpha, Omega, PreviousTheta, Theta};
+use crate::race::track::Track;
+
+pub(crate) const GRAVITY: f32 = 9.81;
+/// Slip angles are worked out against at least this forward speed, so a car
+/// that's nearly stopped doesn't get huge sideways forces from tiny drift.
+const MIN_SLIP_SPEED: f32 = 2.;
+/// Below this forward speed, braking turns into reversing.
+const REVERSE_SPEED: f32 = 0.5;
+/// Grip and extra rolling resistance off the road.
+const GRASS_GRIP: f32 = 0.6;
+const GRASS_ROLLING: f32 = 8.;
+const GRAVEL_GRIP: f32 = 0.45;
+const GRAVEL_ROLLING: f32 = 30.;
+
+/// What a driver asks of the car this tick, whether from the keyboard or not.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct CarControls {
+    /// From 0 to 1.
+    pub throttle: f32,
+    /// From 0 to 1. Reverses once the car has stopped.
+    pub brake: f32,
+    /// From -1 for full right lock to 1 for full left.
+    pub steer: f32,
+}
+
+/// How a car is built and how its tyres grip, in SI units. The car's heading
+/// is its [`Theta`]: 0 faces along +x.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct CarSpec {
+    pub mass: f32,
+    /// Moment of inertia about the vertical axis.
+    pub inertia: f32,
+    pub length: f32,
+    pub width: f32,
+    /// Distance from the centre of mass to the front axle.
+    pub front: f32,
+    /// Distance from the centre of mass to the rear axle.
+    pub rear: f32,
+    /// Drive force at full throttle.
+    pub engine: f32,
+    /// Braking force at full brake.
+    pub brakes: f32,
+    /// Drive force backwards at full brake once stopped.
+    pub reverse: f32,
+    /// Front wheel angle at full lock, in radians, at a standstill.
+    pub max_steer: f32,
+    /// Speed at which full lock is halved, so steering gets gentler as the car
+    /// goes faster.
+    pub steer_falloff: f32,
+    /// Sideways tyre force per radian of slip angle, front and rear.
+    pub cornering_front: f32,
+    pub cornering_rear: f32,
+    /// Tyre force as a multiple of the load on it, at most.
+    pub grip: f32,
+    /// Air drag per square of speed in metres per second.
+    pub drag: f32,
+    /// Rolling resistance per metre per second of speed.
+    pub rolling: f32,
+}
+
+/// One car on the track. Player cars also get `Driver`.
+#[derive(Bundle, Clone)]
+pub struct Car {
+    pub spec: CarSpec,
+    pub controls: CarControls,
+    pub pos: Position,
+    pub pre: PreviousPosition,
+    pub vel: Velocity,
+    pub acc: Acceleration,
+    pub theta: Theta,
+    pub pre_theta: PreviousTheta,
+    pub omega: Omega,
+    pub alpha: Alpha,
+}
+
+/// Marks the car the keyboard drives.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct Driver;
+
+/// Where a car lines up on the grid, counting back from the front.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Default, Deref)]
+pub struct GridSlot(pub usize);
+
+impl Default for CarSpec {
+    fn default() -> Self {
+        Self {
+            mass: 1200.,
+            inertia: 1800.,
+            length: 4.4,
+            width: 1.8,
+            front: 1.2,
+            rear: 1.4,
+            engine: 6000.,
+            brakes: 12000.,
+            reverse: 2500.,
+            max_steer: 0.6,
+            steer_falloff: 20.,
+            cornering_front: 80000.,
+            cornering_rear: 90000.,
+            grip: 1.1,
+            drag: 0.45,
+            rolling: 30.,
+        }
+    }
+}
+
+impl Car {
+    /// A car at rest at `pos`, facing `heading`.
+    pub fn new(spec: CarSpec, pos: Vec2, heading: f32) -> Self {
+        Self {
+            spec,
+            controls: CarControls::default(),
+            pos: Position(pos),
+            pre: PreviousPosition(pos),
+            vel: Velocity::default(),
+            acc: Acceleration::default(),
+            theta: Theta(heading),
+            pre_theta: PreviousTheta(heading),
+            omega: Omega::default(),
+            alpha: Alpha::default(),
+        }
+    }
+}
+
+impl CarSpec {
+    /// The force along and across the car, in its own frame with +x forward and
+    /// +y to the left, and the turning moment, for a car moving at `vel` and
+    /// turning at `omega` in that frame. A bicycle model: each axle is one
+    /// wheel whose sideways force grows with its slip angle until the tyre lets
+    /// go at `grip` times its load.
+    pub fn forces(&self, controls: &CarControls, vel: Vec2, omega: f32, surface: Surface) -> (Vec2, f32) {
+        let speed = vel.length();
+        let steer = controls.steer.clamp(-1., 1.) * self.max_steer / (1. + speed / self.steer_falloff);
+        let slip_speed = vel.x.abs().max(MIN_SLIP_SPEED);
+        let slip_front = ((vel.y + self.front * omega) / slip_speed).atan() - steer * vel.x.signum();
+        let slip_rear = ((vel.y - self.rear * omega) / slip_speed).atan();
+
+        let wheelbase = self.front + self.rear;
+        let load_front = self.mass * GRAVITY * self.rear / wheelbase;
+        let load_rear = self.mass * GRAVITY * self.front / wheelbase;
+        let grip = self.grip * surface.grip;
+        let lateral_front = (-self.cornering_front * slip_front).clamp(-grip * load_front, grip * load_front);
+        let lateral_rear = (-self.cornering_rear * slip_rear).clamp(-grip * load_rear, grip * load_rear);
+
+        let throttle = controls.throttle.clamp(0., 1.);
+        let brake = controls.brake.clamp(0., 1.);
+        // The engine only drives the rear wheels but the brakes work on all four,
+        // and whichever pedal points against the way the car is rolling brakes.
+        let (drive, braking) = if vel.x > REVERSE_SPEED {
+            (throttle * self.engine, brake * self.brakes)
+        } else if vel.x < -REVERSE_SPEED {
+            (-brake * self.reverse, throttle * self.brakes)
+        } else {
+            (throttle * self.engine - brake * self.reverse, 0.)
+        };
+        let braking = braking.min(grip * self.mass * GRAVITY) * vel.x.signum();
+        let resistance = self.drag * vel.x * vel.x.abs() + self.rolling * surface.rolling * vel.x;
+        let longitudinal = drive.clamp(-grip * load_rear, grip * load_rear) - braking - resistance;
+
+        let force = Vec2::new(
+            longitudinal - lateral_front * steer.sin(),
+            lateral_rear + lateral_front * steer.cos(),
+        );
+        let torque = self.front * lateral_front * steer.cos() - self.rear * lateral_rear;
+        (force, torque)
+    }
+}
+
+/// How the ground under a car changes its tyres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Surface {
+    /// Multiplies the tyres' grip.
+    pub grip: f32,
+    /// Multiplies rolling resistance.
+    pub rolling: f32,
+}
+
+impl Surface {
+    pub const ASPHALT: Self = Self { grip: 1., rolling: 1. };
+    pub const GRASS: Self = Self { grip: GRASS_GRIP, rolling: GRASS_ROLLING };
+    pub const GRAVEL: Self = Self { grip: GRAVEL_GRIP, rolling: GRAVEL_ROLLING };
+}
+
+/// Turn every car's controls into acceleration for the next tick.
+pub fn drive_cars(
+    track: Res<Track>,
+    query: Query<(&CarSpec, &CarControls, &Position, &Velocity, &mut Acceleration, &Theta, &Omega, &mut Alpha)>,
+) {
+    for (spec, controls, pos, vel, mut acc, theta, omega, mut alpha) in query {
+        let surface = track.ground(**pos).surface();
+        let heading = Vec2::from_angle(**theta);
+        let local_vel = Vec2::new(vel.dot(heading), vel.dot(heading.perp()));
+        let (force, torque) = spec.forces(controls, local_vel, **omega, surface);
+        acc.0 += heading.rotate(force) / spec.mass;
+        **alpha += torque / spec.inertia;
+    }
+}
@@ -0,0 +1,415 @@
+use bevy::prelude::*;
+
+use crate::fruit::collision::{accumulate_impulse, candidate_pairs};
+use crate::fruit::pva::{Position, PreviousPosition, Velocity};
+use crate::fruit::toa::{Omega, Theta};
+use crate::race::car::CarSpec;
+use crate::race::track::{BARRIER_WIDTH, Track};
+
+/// Share of the closing speed two cars, or a car and a barrier, part with.
+const BOUNCE: f32 = 0.3;
+/// Closing speeds below this, in metres per second, don't bounce at all, so
+/// cars leaning on each other or on a barrier don't jitter.
+const MIN_BOUNCE_SPEED: f32 = 1.;
+/// Sliding friction between bodywork, as a share of how hard it's pressed.
+const CAR_FRICTION: f32 = 0.5;
+const BARRIER_FRICTION: f32 = 0.3;
+/// Rounds of sequential impulses per tick.
+const ITERATIONS: usize = 8;
+/// Overlap, in metres, left alone rather than pushed out, and the share of
+/// the rest pushed out each tick.
+const SLOP: f32 = 0.01;
+const CORRECTION: f32 = 0.8;
+
+/// Two cars, or a car and a barrier, hit each other this tick.
+#[derive(Debug, Event, Clone, PartialEq)]
+pub struct CollisionEvent {
+    pub car: Entity,
+    /// The car it hit, or `None` for a barrier.
+    pub other: Option<Entity>,
+    /// Where they touched.
+    pub points: Vec<Vec2>,
+    /// Unit vector from `car` toward what it hit.
+    pub normal: Vec2,
+    /// Impulse, in newton seconds, that pushed them apart, and that friction
+    /// took out of them sliding along each other.
+    pub impulse: f32,
+    pub friction: f32,
+}
+
+/// A car as the contact solver sees it: a box `half` its length by `half` its
+/// width, facing `axis`.
+struct Body {
+    inv_mass: f32,
+    inv_inertia: f32,
+    half: Vec2,
+    axis: Vec2,
+    pos: Vec2,
+    vel: Vec2,
+    omega: f32,
+}
+
+/// A corner, or a barrier end, where bodywork meets, and the impulses summed
+/// there over the iterations so far.
+struct ContactPoint {
+    pos: Vec2,
+    /// How fast the bodywork should come away here after the hit, by
+    /// [`BOUNCE`].
+    target: f32,
+    normal_impulse: f32,
+    tangent_impulse: f32,
+}
+
+/// Car `a` against car `b`, or against a barrier when `b` is `None`, at up to
+/// four points. `normal` points out of car `a` into what it hit.
+struct Contact {
+    a: usize,
+    b: Option<usize>,
+    normal: Vec2,
+    depth: f32,
+    points: Vec<ContactPoint>,
+}
+
+impl Body {
+    fn new(spec: &CarSpec, pos: Vec2, vel: Vec2, theta: f32, omega: f32) -> Self {
+        Self {
+            inv_mass: 1. / spec.mass,
+            inv_inertia: 1. / spec.inertia,
+            half: Vec2::new(spec.length, spec.width) / 2.,
+            axis: Vec2::from_angle(theta),
+            pos,
+            vel,
+            omega,
+        }
+    }
+
+    /// `point` in the body's own frame, with +x forward.
+    fn to_local(&self, point: Vec2) -> Vec2 {
+        let d = point - self.pos;
+        Vec2::new(d.dot(self.axis), d.dot(self.axis.perp()))
+    }
+
+    fn corners(&self) -> [Vec2; 4] {
+        let (x, y) = (self.axis * self.half.x, self.axis.perp() * self.half.y);
+        [self.pos + x + y, self.pos - x + y, self.pos - x - y, self.pos + x - y]
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        let local = self.to_local(point);
+        local.x.abs() <= self.half.x && local.y.abs() <= self.half.y
+    }
+
+    /// Half the length of the box's shadow along the unit vector `axis`.
+    fn reach(&self, axis: Vec2) -> f32 {
+        self.half.x * axis.dot(self.axis).abs() + self.half.y * axis.dot(self.axis.perp()).abs()
+    }
+
+    /// Velocity of the bodywork at `point`.
+    fn point_vel(&self, point: Vec2) -> Vec2 {
+        self.vel + (point - self.pos).perp() * self.omega
+    }
+
+    /// Knock the car by `impulse` at `point` on its bodywork. Anywhere but
+    /// in line with its middle, that sets it spinning as well.
+    fn apply(&mut self, point: Vec2, impulse: Vec2) {
+        self.vel += impulse * self.inv_mass;
+        self.omega += (point - self.pos).perp_dot(impulse) * self.inv_inertia;
+    }
+
+    /// How much speed the bodywork at `point` picks up along `direction`
+    /// per unit of impulse there, counting the spin it sets off.
+    fn give(&self, point: Vec2, direction: Vec2) -> f32 {
+        let arm = (point - self.pos).perp_dot(direction);
+        self.inv_mass + arm * arm * self.inv_inertia
+    }
+}
+
+impl ContactPoint {
+    fn new(pos: Vec2) -> Self {
+        Self { pos, target: 0., normal_impulse: 0., tangent_impulse: 0. }
+    }
+}
+
+impl Contact {
+    fn relative_vel(&self, bodies: &[Body], point: Vec2) -> Vec2 {
+        let a = bodies[self.a].point_vel(point);
+        let b = self.b.map_or(Vec2::ZERO, |b| bodies[b].point_vel(point));
+        b - a
+    }
+
+    fn give(&self, bodies: &[Body], point: Vec2, direction: Vec2) -> f32 {
+        bodies[self.a].give(point, direction) + self.b.map_or(0., |b| bodies[b].give(point, direction))
+    }
+
+    fn apply(&self, bodies: &mut [Body], point: Vec2, impulse: Vec2) {
+        bodies[self.a].apply(point, -impulse);
+        if let Some(b) = self.b {
+            bodies[b].apply(point, impulse);
+        }
+    }
+
+    /// Aim to part at `BOUNCE` times the speed the pair first closed at.
+    fn set_bounce(&mut self, bodies: &[Body]) {
+        for i in 0..self.points.len() {
+            let closing = self.relative_vel(bodies, self.points[i].pos).dot(self.normal);
+            if closing < -MIN_BOUNCE_SPEED {
+                self.points[i].target = -BOUNCE * closing;
+            }
+        }
+    }
+
+    /// One sequential-impulse pass, as in the fruit solver, at each corner in
+    /// contact.
+    fn solve(&mut self, bodies: &mut [Body], friction: f32) {
+        let tangent = self.normal.perp();
+        for i in 0..self.points.len() {
+            let pos = self.points[i].pos;
+            let closing = self.relative_vel(bodies, pos).dot(self.normal);
+            let give = self.give(bodies, pos, self.normal);
+            let point = &mut self.points[i];
+            let impulse = accumulate_impulse(&mut point.normal_impulse, (point.target - closing) / give, 0., f32::INFINITY);
+            self.apply(bodies, pos, self.normal * impulse);
+
+            let slide = self.relative_vel(bodies, pos).dot(tangent);
+            let give = self.give(bodies, pos, tangent);
+            let point = &mut self.points[i];
+            let limit = friction * point.normal_impulse;
+            let impulse = accumulate_impulse(&mut point.tangent_impulse, -slide / give, -limit, limit);
+            self.apply(bodies, pos, tangent * impulse);
+        }
+    }
+
+    /// Undo [`CORRECTION`] of how far car `a` has gone into the other car or
+    /// the barrier, past [`SLOP`]. Between two cars, the lighter one moves
+    /// further; a barrier doesn't move, so the car takes all of it.
+    fn separate(&self, bodies: &mut [Body]) {
+        let give = bodies[self.a].inv_mass + self.b.map_or(0., |b| bodies[b].inv_mass);
+        let push = CORRECTION * (self.depth - SLOP).max(0.);
+        if push <= 0. {
+            return;
+        }
+        let shift = self.normal * push / give;
+        bodies[self.a].pos -= shift * bodies[self.a].inv_mass;
+        if let Some(b) = self.b {
+            bodies[b].pos += shift * bodies[b].inv_mass;
+        }
+    }
+}
+
+/// Where boxes `a` and `b` overlap, if they do: the normal from `a` toward `b`,
+/// how deep, and the corners of either that are inside the other. By the
+/// separating axis test, the boxes overlap unless one of their four edge
+/// directions has a gap between their shadows, and they come apart quickest
+/// along the direction where the shadows overlap least.
+fn box_contact(a: &Body, b: &Body) -> Option<(Vec2, f32, Vec<Vec2>)> {
+    let d = b.pos - a.pos;
+    let mut best: Option<(Vec2, f32)> = None;
+    for axis in [a.axis, a.axis.perp(), b.axis, b.axis.perp()] {
+        let overlap = a.reach(axis) + b.reach(axis) - d.dot(axis).abs();
+        if overlap <= 0. {
+            return None;
+        }
+        if best.is_none_or(|(_, depth)| overlap < depth) {
+            let normal = if d.dot(axis) < 0. { -axis } else { axis };
+            best = Some((normal, overlap));
+        }
+    }
+    let (normal, depth) = best?;
+    let mut points: Vec<Vec2> = b.corners().into_iter().filter(|&corner| a.contains(corner)).collect();
+    points.extend(a.corners().into_iter().filter(|&corner| b.contains(corner)));
+    if points.is_empty() {
+        // Crossed edges with no corner inside: touch in the middle.
+        points.push((a.pos + b.pos) / 2.);
+    }
+    Some((normal, depth, points))
+}
+
+/// Where `body` overlaps the barrier segment from `start` to `end`, if it
+/// does: the normal from the body toward the barrier, how deep, and the
+/// corners that went through. The body is kept to the side its middle was on
+/// at `pre`, however far it went through this tick.
+fn barrier_contact(body: &Body, pre: Vec2, start: Vec2, end: Vec2) -> Option<(Vec2, f32, Vec<Vec2>)> {
+    let length = start.distance(end);
+    if length <= 0. {
+        return None;
+    }
+    let along = (end - start) / length;
+    let side = along.perp() * (pre - start).dot(along.perp()).signum();
+    let mut depth: f32 = 0.;
+    let mut points = vec![];
+    for corner in body.corners() {
+        let t = (corner - start).dot(along);
+        let inside = BARRIER_WIDTH / 2. - (corner - start).dot(side);
+        if (0. ..=length).contains(&t) && inside > 0. {
+            depth = depth.max(inside);
+            points.push(corner);
+        }
+    }
+    (!points.is_empty()).then_some((-side, depth, points))
+}
+
+/// Where the open end `end` of a barrier pokes into the side of `body`, which
+/// no corner going through the barrier would catch.
+fn end_contact(body: &Body, end: Vec2) -> Option<(Vec2, f32, Vec<Vec2>)> {
+    let local = body.to_local(end);
+    let inside = body.half + BARRIER_WIDTH / 2. - local.abs();
+    if inside.x <= 0. || inside.y <= 0. {
+        return None;
+    }
+    let (normal, depth) = if inside.x < inside.y {
+        (body.axis * local.x.signum(), inside.x)
+    } else {
+        (body.axis.perp() * local.y.signum(), inside.y)
+    };
+    Some((normal, depth, vec![end]))
+}
+
+/// A car against several segments of one barrier, as a single contact at
+/// every point that went through, along the deepest segment's normal. Pushed
+/// out of each segment in turn, a car up against a join in the barrier
+/// would be pushed out once for every segment it touches.
+fn merge_contacts(hits: Vec<(Vec2, f32, Vec<Vec2>)>) -> Option<(Vec2, f32, Vec<Vec2>)> {
+    hits.into_iter().reduce(|(normal, depth, mut points), (other_normal, other_depth, other_points)| {
+        for point in other_points {
+            // Corners on a join between segments go through both.
+            if !points.iter().any(|&seen| seen.distance(point) < 1e-4) {
+                points.push(point);
+            }
+        }
+        if other_depth > depth {
+            (other_normal, other_depth, points)
+        } else {
+            (normal, depth, points)
+        }
+    })
+}
+
+/// Find where cars have run into each other or the barriers this tick, and
+/// knock them all apart together over [`ITERATIONS`] passes. Off-centre hits
+/// set cars spinning.
+pub fn solve_collisions(
+    track: Res<Track>,
+    mut collisions: EventWriter<CollisionEvent>,
+    mut query: Query<(Entity, &CarSpec, &PreviousPosition, &mut Position, &mut Velocity, &Theta, &mut Omega)>,
+) {
+    let mut entities = vec![];
+    let mut pres = vec![];
+    let mut bodies = vec![];
+    for (entity, spec, pre, pos, vel, theta, omega) in &query {
+        entities.push(entity);
+        pres.push(**pre);
+        bodies.push(Body::new(spec, **pos, **vel, **theta, **omega));
+    }
+
+    let mut contacts = vec![];
+    let mut touch = |a: usize, b: Option<usize>, (normal, depth, points): (Vec2, f32, Vec<Vec2>)| {
+        let points = points.into_iter().map(ContactPoint::new).collect();
+        contacts.push(Contact { a, b, normal, depth, points });
+    };
+    let circles: Vec<(Vec2, f32)> = bodies.iter().map(|body| (body.pos, body.half.length())).collect();
+    for (i, j) in candidate_pairs(&circles) {
+        if let Some(contact) = box_contact(&bodies[i], &bodies[j]) {
+            touch(i, Some(j), contact);
+        }
+    }
+    for (i, body) in bodies.iter().enumerate() {
+        let near = |point: Vec2| point.distance(body.pos) < body.half.length() + BARRIER_WIDTH / 2.;
+        for barrier in &track.barriers {
+            let mut hits = vec![];
+            for (start, end) in barrier.segments() {
+                let t = (body.pos - start).dot(end - start) / start.distance_squared(end);
+                let closest = start.lerp(end, t.clamp(0., 1.));
+                if near(closest) {
+                    hits.extend(barrier_contact(body, pres[i], start, end));
+                }
+            }
+            if !barrier.closed {
+                for &end in [barrier.points.first(), barrier.points.last()].into_iter().flatten() {
+                    hits.extend(end_contact(body, end));
+                }
+            }
+            if let Some(contact) = merge_contacts(hits) {
+                touch(i, None, contact);
+            }
+        }
+    }
+
+    for contact in &mut contacts {
+        contact.set_bounce(&bodies);
+    }
+    for _ in 0..ITERATIONS {
+        for contact in &mut contacts {
+            let friction = if contact.b.is_some() { CAR_FRICTION } else { BARRIER_FRICTION };
+            contact.solve(&mut bodies, friction);
+        }
+    }
+    for contact in &contacts {
+        contact.separate(&mut bodies);
+        collisions.write(CollisionEvent {
+            car: entities[contact.a],
+            other: contact.b.map(|b| entities[b]),
+            points: contact.points.iter().map(|point| point.pos).collect(),
+            normal: contact.normal,
+            impulse: contact.points.iter().map(|point| point.normal_impulse).sum(),
+            friction: contact.points.iter().map(|point| point.tangent_impulse.abs()).sum(),
+        });
+    }
+
+    for (i, body) in bodies.iter().enumerate() {
+        let Ok((_, _, _, mut pos, mut vel, _, mut omega)) = query.get_mut(entities[i]) else {
+            continue;
+        };
+        pos.0 = body.pos;
+        vel.0 = body.vel;
+        **omega = body.omega;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn body(pos: Vec2, theta: f32) -> Body {
+        Body::new(&CarSpec::default(), pos, Vec2::ZERO, theta, 0.)
+    }
+
+    #[test]
+    fn test_box_contact() {
+        let spec = CarSpec::default();
+        let a = body(Vec2::ZERO, 0.);
+        // Nose to tail, just overlapping.
+        let (normal, depth, points) = box_contact(&a, &body(Vec2::new(spec.length - 0.1, 0.2), 0.)).unwrap();
+        assert!(normal.distance(Vec2::X) < 1e-5, "{normal}");
+        assert!((depth - 0.1).abs() < 1e-4, "{depth}");
+        assert_eq!(points.len(), 2);
+        assert!(points.iter().all(|point| (point.x - spec.length / 2.).abs() < 0.2));
+        // A corner into the side: the normal is the side's.
+        let b = body(Vec2::new(0., spec.width / 2. + 1.5), 1.);
+        let (normal, _, points) = box_contact(&a, &b).unwrap();
+        assert!(normal.distance(Vec2::Y) < 1e-5, "{normal}");
+        assert_eq!(points.len(), 1);
+        assert!(a.contains(points[0]));
+        // Side by side with a gap.
+        assert!(box_contact(&a, &body(Vec2::new(1., spec.width + 0.1), 0.)).is_none());
+        assert!(box_contact(&a, &body(Vec2::new(spec.length + 0.5, 0.), 0.3)).is_none());
+    }
+
+    #[test]
+    fn test_barrier_contacts_merge() {
+        let spec = CarSpec::default();
+        // Nosed into a straight barrier right across the join of two segments,
+        // each with one corner through it.
+        let y = BARRIER_WIDTH / 2. + spec.width / 2. - 0.1;
+        let car = body(Vec2::new(0., -y), 0.);
+        let pre = Vec2::new(0., -y - 1.);
+        let left = barrier_contact(&car, pre, Vec2::new(-10., 0.), Vec2::ZERO).unwrap();
+        let right = barrier_contact(&car, pre, Vec2::ZERO, Vec2::new(10., 0.)).unwrap();
+        assert_eq!((left.2.len(), right.2.len()), (1, 1));
+
+        let (normal, depth, points) = merge_contacts(vec![left, right]).unwrap();
+        assert!(normal.distance(Vec2::Y) < 1e-5, "{normal}");
+        assert!((depth - 0.1).abs() < 1e-4, "{depth}");
+        assert_eq!(points.len(), 2);
+        assert!(merge_contacts(vec![]).is_none());
+    }
+}
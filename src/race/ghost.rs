@@ -0,0 +1,229 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::fruit::pva::Position;
+use crate::fruit::toa::Theta;
+use crate::race::car::{CarSpec, Driver};
+use crate::race::lap::{LapEvent, LapTimer};
+use crate::race::track::Track;
+
+/// The fastest lap driven on a track: its times and where the car was on every
+/// fixed tick of it.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Ghost {
+    pub track: String,
+    /// [`Track::geometry`] of the track the lap was driven on.
+    #[serde(default)]
+    pub geometry: u64,
+    pub timestep: Duration,
+    pub time: f32,
+    pub sectors: Vec<f32>,
+    pub frames: Vec<GhostFrame>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct GhostFrame {
+    pub pos: (f32, f32),
+    pub theta: f32,
+}
+
+/// The best lap on the current track, kept in a file per track under `dir`
+/// between runs.
+#[derive(Debug, Resource, Clone, PartialEq, Default)]
+pub struct BestLap {
+    pub dir: Option<PathBuf>,
+    pub ghost: Option<Ghost>,
+}
+
+/// Where the driver has been on every tick of the lap so far.
+#[derive(Debug, Component, Clone, PartialEq, Default)]
+pub struct GhostRecorder {
+    pub frames: Vec<GhostFrame>,
+}
+
+/// The translucent car that drives the best lap alongside the player.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct GhostCar;
+
+impl BestLap {
+    /// `ghosts` under the platform data dir, e.g. `~/.local/share/drive` on
+    /// Linux.
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("drive").join("ghosts"))
+    }
+
+    /// The file for `track` under `dir`.
+    pub fn path(dir: &Path, track: &str) -> PathBuf {
+        let name: String = track.chars()
+            .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+            .collect();
+        dir.join(format!("{name}.ghost.ron"))
+    }
+
+    /// Read the best lap on `track` from `dir`. A missing or unreadable file
+    /// gives no best lap, and is overwritten by the next one.
+    pub fn load(dir: Option<PathBuf>, track: &str) -> Self {
+        let ghost = dir.as_deref().and_then(|dir| {
+            let path = Self::path(dir, track);
+            Self::read(&path)
+                .inspect_err(|error| match error.kind() {
+                    io::ErrorKind::NotFound => {}
+                    _ => warn!("ignoring best lap in {}: {error}", path.display()),
+                })
+                .ok()
+        });
+        Self { dir, ghost }
+    }
+
+    fn read(path: &Path) -> io::Result<Ghost> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(io::Error::other)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let (Some(dir), Some(ghost)) = (&self.dir, &self.ghost) else {
+            return Ok(());
+        };
+        fs::create_dir_all(dir)?;
+        let text = ron::ser::to_string_pretty(ghost, Default::default()).map_err(io::Error::other)?;
+        fs::write(Self::path(dir, &ghost.track), text)
+    }
+
+    /// Keep `ghost` if it beats the best lap so far, and say whether it did.
+    pub fn offer(&mut self, ghost: Ghost) -> bool {
+        let faster = self.ghost.as_ref().is_none_or(|best| ghost.time < best.time);
+        if faster {
+            self.ghost = Some(ghost);
+        }
+        faster
+    }
+}
+
+/// Record where the driver is on every tick of a lap, and keep the lap as the
+/// new ghost if it was the fastest yet.
+pub fn record_ghost(
+    fixed_time: Res<Time<Fixed>>,
+    track: Res<Track>,
+    mut best: ResMut<BestLap>,
+    mut laps: EventReader<LapEvent>,
+    driver: Single<(Entity, &Position, &Theta, &LapTimer, &mut GhostRecorder), With<Driver>>,
+) {
+    let (entity, pos, theta, timer, mut recorder) = driver.into_inner();
+    if let Some(lap) = laps.read().find(|lap| lap.car == entity) {
+        let ghost = Ghost {
+            track: track.name.clone(),
+            geometry: track.geometry(),
+            timestep: fixed_time.timestep(),
+            time: lap.time,
+            sectors: lap.sectors.clone(),
+            frames: std::mem::take(&mut recorder.frames),
+        };
+        if best.offer(ghost) && let Err(error) = best.save() {
+            warn!("could not save best lap: {error}");
+        }
+    }
+    if timer.time.is_none() || timer.just_started() {
+        recorder.frames.clear();
+    }
+    if timer.time.is_some() {
+        recorder.frames.push(GhostFrame { pos: pos.0.into(), theta: **theta });
+    }
+}
+
+/// Load the best lap for the track whenever the track changes. Laps driven at
+/// another timestep, or on another track under the same name, are dropped.
+pub fn load_best_lap(
+    track: Res<Track>,
+    mut best: ResMut<BestLap>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    *best = BestLap::load(best.dir.clone(), &track.name);
+    if let Some(ghost) = &best.ghost {
+        if ghost.timestep != fixed_time.timestep() {
+            warn!("ignoring best lap recorded at a different timestep");
+            best.ghost = None;
+        } else if ghost.geometry != track.geometry() {
+            warn!("ignoring best lap driven on another layout of {}", track.name);
+            best.ghost = None;
+        } else {
+            info!("best lap on {} is {:.3}s", track.name, ghost.time);
+        }
+    }
+}
+
+pub fn load_ghost_car(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let spec = CarSpec::default();
+    commands.spawn((
+        GhostCar,
+        Mesh2d(meshes.add(Rectangle::new(spec.length, spec.width))),
+        MeshMaterial2d(materials.add(Color::srgba(1., 1., 1., 0.35))),
+        Transform::from_xyz(0., 0., 0.9),
+        Visibility::Hidden,
+    ));
+}
+
+/// Put the ghost where the best lap was at this point in the driver's lap, or
+/// hide it if there's no best lap or the driver's lap is already longer.
+pub fn show_ghost(
+    fixed_time: Res<Time<Fixed>>,
+    best: Res<BestLap>,
+    driver: Single<&GhostRecorder, With<Driver>>,
+    ghost_car: Single<(&mut Transform, &mut Visibility), With<GhostCar>>,
+) {
+    let (mut transform, mut visibility) = ghost_car.into_inner();
+    let frame = driver.frames.len().checked_sub(1);
+    let frames = best.ghost.as_ref().map_or(&[][..], |ghost| &ghost.frames[..]);
+    let Some(frame) = frame.filter(|&frame| frame < frames.len()) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    let (pre, now) = (frames[frame.saturating_sub(1)], frames[frame]);
+    let alpha = fixed_time.overstep_fraction();
+    transform.translation = Vec2::from(pre.pos).lerp(Vec2::from(now.pos), alpha).extend(0.9);
+    transform.rotation = Quat::from_rotation_z(pre.theta.lerp(now.theta, alpha));
+    *visibility = Visibility::Inherited;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ghost(time: f32) -> Ghost {
+        Ghost {
+            track: "Test Track".to_string(),
+            geometry: 1,
+            timestep: Duration::from_micros(15_625),
+            time,
+            sectors: vec![time / 2., time / 2.],
+            frames: vec![GhostFrame { pos: (1., 2.), theta: 0.5 }; 3],
+        }
+    }
+
+    #[test]
+    fn test_best_lap_round_trip() {
+        let dir = std::env::temp_dir().join("drive_test_ghosts");
+        let _ = fs::remove_dir_all(&dir);
+        let mut best = BestLap::load(Some(dir.clone()), "Test Track");
+        assert_eq!(best.ghost, None);
+
+        assert!(best.offer(ghost(60.)));
+        assert!(!best.offer(ghost(61.)));
+        assert!(best.offer(ghost(59.)));
+        best.save().unwrap();
+        assert!(BestLap::path(&dir, "Test Track").ends_with("test_track.ghost.ron"));
+
+        let loaded = BestLap::load(Some(dir.clone()), "Test Track");
+        assert_eq!(loaded.ghost, Some(ghost(59.)));
+        assert_eq!(BestLap::load(Some(dir.clone()), "Other").ghost, None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
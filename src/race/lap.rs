@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+
+use crate::fruit::pva::Position;
+use crate::race::track::Track;
+
+/// Further than this along the centreline in one tick, in metres, and the car
+/// must have cut across to another part of the track rather than driven there.
+const MAX_STEP: f32 = 25.;
+
+/// Lap and sector times for one car. Timing starts the first time the car
+/// crosses the start line, and a lap only counts if the car passed every
+/// checkpoint in order since then.
+#[derive(Debug, Component, Clone, PartialEq, Default)]
+pub struct LapTimer {
+    /// Laps completed.
+    pub laps: u32,
+    /// Seconds into the current lap, if timing has started.
+    pub time: Option<f32>,
+    pub last: Option<f32>,
+    pub best: Option<f32>,
+    /// Seconds into the current lap at each checkpoint passed so far.
+    pub splits: Vec<f32>,
+    /// Sector times of the last lap, from the start line to the first
+    /// checkpoint, between checkpoints, and from the last back to the line.
+    pub last_sectors: Vec<f32>,
+    /// Fastest time through each sector on any lap that counted.
+    pub best_sectors: Vec<f32>,
+    /// The car skipped a checkpoint, so this lap won't count.
+    pub cut: bool,
+    /// Distance along the track at the last tick.
+    s: Option<f32>,
+}
+
+/// A car finished lap `lap` in `time` seconds.
+#[derive(Debug, Event, Clone, PartialEq)]
+pub struct LapEvent {
+    pub car: Entity,
+    pub lap: u32,
+    pub time: f32,
+    pub sectors: Vec<f32>,
+}
+
+impl LapTimer {
+    /// Move the car on to distance `s` along a track `length` long with
+    /// `checkpoints` at the given distances, `dt` seconds after the last tick.
+    /// Returns the time of the lap just finished.
+    pub fn advance(&mut self, s: f32, length: f32, checkpoints: &[f32], dt: f32) -> Option<f32> {
+        if let Some(time) = &mut self.time {
+            *time += dt;
+        }
+        let before = self.s.replace(s)?;
+        let step = (s - before + length / 2.).rem_euclid(length) - length / 2.;
+        if step <= 0. || step > MAX_STEP {
+            return None;
+        }
+        // Whether the car went forward over the line `at` along the track.
+        let crossed = |at: f32| {
+            let ahead = (at - before).rem_euclid(length);
+            ahead > 0. && ahead <= step
+        };
+
+        if let Some(time) = self.time {
+            for (i, &at) in checkpoints.iter().enumerate().skip(self.splits.len()) {
+                if crossed(at) {
+                    if i == self.splits.len() {
+                        self.splits.push(time);
+                    } else {
+                        self.cut = true;
+                    }
+                }
+            }
+        }
+        if !crossed(0.) {
+            return None;
+        }
+
+        let finished = self.time.filter(|_| !self.cut && self.splits.len() == checkpoints.len());
+        if let Some(time) = finished {
+            let mut sectors: Vec<_> = self.splits.iter().chain([&time])
+                .scan(0., |start, &end| Some(end - std::mem::replace(start, end)))
+                .collect();
+            self.laps += 1;
+            self.last = Some(time);
+            self.best = Some(self.best.map_or(time, |best| best.min(time)));
+            self.best_sectors.resize(sectors.len(), f32::INFINITY);
+            for (best, &sector) in self.best_sectors.iter_mut().zip(&sectors) {
+                *best = best.min(sector);
+            }
+            std::mem::swap(&mut self.last_sectors, &mut sectors);
+        }
+        // Backing over the line and forward again carries on with the lap, but
+        // anything else starts a new one.
+        if finished.is_some() || self.time.is_none() || self.cut || !self.splits.is_empty() {
+            self.time = Some(0.);
+            self.splits.clear();
+            self.cut = false;
+        }
+        finished
+    }
+
+    /// Whether the car crossed the start line into a new lap this tick.
+    pub fn just_started(&self) -> bool {
+        self.time == Some(0.)
+    }
+}
+
+pub fn time_laps(
+    time: Res<Time>,
+    track: Res<Track>,
+    mut laps: EventWriter<LapEvent>,
+    query: Query<(Entity, &Position, &mut LapTimer)>,
+) {
+    let length = track.length();
+    for (car, pos, mut timer) in query {
+        let s = track.locate(**pos).s;
+        if let Some(time) = timer.advance(s, length, &track.checkpoints, time.delta_secs()) {
+            laps.write(LapEvent { car, lap: timer.laps, time, sectors: timer.last_sectors.clone() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Ten metres a second round a kilometre of track, `ticks` times.
+    fn drive(s: &mut f32, checkpoints: &[f32], ticks: usize, timer: &mut LapTimer) -> Option<f32> {
+        let mut finished = None;
+        for _ in 0..ticks {
+            *s = (*s + 10.) % 1000.;
+            finished = finished.or(timer.advance(*s, 1000., checkpoints, 1.));
+        }
+        finished
+    }
+
+    #[test]
+    fn test_lap_needs_the_far_side() {
+        let mut timer = LapTimer::default();
+        let mut s = 950.;
+        // From the grid over the line starts the clock.
+        assert_eq!(drive(&mut s, &[500.], 10, &mut timer), None);
+        assert_eq!(timer.time, Some(5.));
+        // Round the lap.
+        assert_eq!(drive(&mut s, &[500.], 100, &mut timer), Some(100.));
+        assert_eq!((timer.laps, timer.best), (1, Some(100.)));
+        assert_eq!(timer.last_sectors, [50., 50.]);
+
+        // Backing over the line and forward again doesn't count.
+        timer.advance(990., 1000., &[500.], 1.);
+        assert_eq!(timer.advance(10., 1000., &[500.], 1.), None);
+        assert_eq!(timer.laps, 1);
+        assert_eq!(timer.time, Some(7.));
+    }
+
+    #[test]
+    fn test_shortcut_does_not_count() {
+        let checkpoints = [300., 600.];
+        let mut timer = LapTimer::default();
+        let mut s = 980.;
+        drive(&mut s, &checkpoints, 20, &mut timer);
+        assert_eq!((timer.time, timer.splits.len()), (Some(18.), 0));
+        // Jump from 180 m to 390 m across the infield, missing the first
+        // checkpoint, and carry on round.
+        s = 380.;
+        assert_eq!(drive(&mut s, &checkpoints, 61, &mut timer), None);
+        assert!(timer.cut);
+        assert_eq!(drive(&mut s, &checkpoints, 1, &mut timer), None);
+        assert_eq!((timer.laps, timer.time, timer.cut), (0, Some(0.), false));
+
+        // A proper lap after that counts.
+        assert_eq!(drive(&mut s, &checkpoints, 100, &mut timer), Some(100.));
+        assert_eq!(timer.last_sectors, [30., 30., 40.]);
+        assert_eq!(timer.best_sectors, [30., 30., 40.]);
+    }
+}
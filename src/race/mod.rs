@@ -0,0 +1,203 @@
+pub mod ai;
+pub mod car;
+pub mod collision;
+pub mod ghost;
+pub mod lap;
+pub(crate) mod sim;
+pub mod track;
+
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use crate::fruit::pva::{Position, PreviousPosition, Velocity};
+use crate::fruit::toa::{PreviousTheta, Theta};
+use car::{CarControls, CarSpec, Driver};
+use ghost::{BestLap, load_best_lap, load_ghost_car, show_ghost};
+use lap::LapTimer;
+use sim::{load_driver, load_opponents};
+use track::{TrackLoader, TrackPath, load_track, load_track_mesh, update_track};
+
+pub use ai::{AiDriver, RacingLine, Skill};
+pub use car::{Car, GridSlot, Surface};
+pub use collision::CollisionEvent;
+pub use ghost::{Ghost, GhostFrame};
+pub use lap::LapEvent;
+pub use sim::{CarState, Opponents, RaceSim, RaceSimPlugin, RaceSimSet, RaceTick, ResetRaceEvent, grid_car};
+pub use track::{Barrier, ControlPoint, Ground, SurfaceZone, Track, TrackFile, TrackHandle, TrackPoint, Zone};
+
+/// Screen pixels per metre.
+const ZOOM: f32 = 8.;
+
+/// A top-down race around a closed track against AI cars. Arrow keys or WASD
+/// drive and R puts every car back on the grid.
+#[derive(Default)]
+pub struct RaceGame {
+    /// Path of the track to race on, relative to the asset folder, instead of
+    /// `tracks/stadium.track.ron`.
+    pub track: Option<String>,
+    /// Where to keep the best lap on each track, instead of
+    /// [`BestLap::default_dir`].
+    pub ghosts: Option<PathBuf>,
+    /// How many AI cars to race against, instead of 3.
+    pub opponents: Option<usize>,
+}
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct LapDisplay;
+
+impl Plugin for RaceGame {
+    fn build(&self, app: &mut App) {
+        let path = self.track.clone().unwrap_or_else(|| "tracks/stadium.track.ron".to_string());
+        app
+        .add_plugins(RaceSimPlugin)
+        .insert_resource(TrackPath(path))
+        .insert_resource(Opponents(self.opponents.unwrap_or(3)))
+        .insert_resource(BestLap { dir: self.ghosts.clone().or_else(BestLap::default_dir), ghost: None })
+        .insert_resource(ClearColor(Ground::default().color()))
+        .init_asset::<Track>()
+        .init_asset_loader::<TrackLoader>()
+        .add_systems(Startup, (load_race_camera, load_track, load_driver, load_opponents, load_ghost_car, load_lap_display))
+        .add_systems(Update, (
+            (
+                update_track.run_if(on_event::<AssetEvent<Track>>),
+                (load_track_mesh, load_best_lap).run_if(resource_changed::<Track>),
+            ).chain(),
+            attach_car_mesh,
+            update_lap_display,
+            reset_input,
+        ))
+        .add_systems(FixedUpdate, car_input.before(RaceSimSet))
+        .add_systems(RunFixedMainLoop, (interpolate_car_transform, show_ghost, follow_driver).chain().in_set(RunFixedMainLoopSystem::AfterFixedMainLoop))
+        ;
+    }
+}
+
+fn load_race_camera(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        Camera2d,
+        Projection::Orthographic(OrthographicProjection {
+            scale: 1. / ZOOM,
+            ..OrthographicProjection::default_2d()
+        }),
+    ));
+}
+
+fn load_lap_display(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        LapDisplay,
+        Text::new(""),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.),
+            left: Val::Px(12.),
+            ..default()
+        },
+    ));
+}
+
+/// Draw every new car as a box with a darker windscreen toward the front.
+fn attach_car_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<(Entity, &CarSpec, &Position, &Theta, Has<Driver>), Without<Mesh2d>>,
+) {
+    for (entity, spec, pos, theta, driver) in &query {
+        let color = if driver { Color::srgb(0.85, 0.1, 0.1) } else { Color::srgb(0.1, 0.3, 0.85) };
+        commands.entity(entity)
+            .insert((
+                Mesh2d(meshes.add(Rectangle::new(spec.length, spec.width))),
+                MeshMaterial2d(materials.add(color)),
+                Transform::from_translation(pos.extend(1.)).with_rotation(Quat::from_rotation_z(**theta)),
+            ))
+            .with_child((
+                Mesh2d(meshes.add(Rectangle::new(spec.length * 0.2, spec.width * 0.8))),
+                MeshMaterial2d(materials.add(Color::srgb(0.1, 0.1, 0.15))),
+                Transform::from_xyz(spec.length * 0.1, 0., 0.1),
+            ));
+    }
+}
+
+fn car_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    controls: Single<&mut CarControls, With<Driver>>,
+) {
+    let pressed = |keys: [KeyCode; 2]| if keyboard_input.any_pressed(keys) { 1. } else { 0. };
+    *controls.into_inner() = CarControls {
+        throttle: pressed([KeyCode::ArrowUp, KeyCode::KeyW]),
+        brake: pressed([KeyCode::ArrowDown, KeyCode::KeyS]),
+        steer: pressed([KeyCode::ArrowLeft, KeyCode::KeyA]) - pressed([KeyCode::ArrowRight, KeyCode::KeyD]),
+    };
+}
+
+fn reset_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut resets: EventWriter<ResetRaceEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        resets.write(ResetRaceEvent);
+    }
+}
+
+fn interpolate_car_transform(
+    fixed_time: Res<Time<Fixed>>,
+    mut query: Query<(&mut Transform, &Position, &PreviousPosition, &Theta, &PreviousTheta), With<CarSpec>>,
+) {
+    let alpha = fixed_time.overstep_fraction();
+    for (mut transform, &pos, &pre, &theta, &pre_theta) in query.iter_mut() {
+        transform.translation = pre.lerp(*pos, alpha).extend(1.);
+        transform.rotation = Quat::from_rotation_z(pre_theta.lerp(*theta, alpha));
+    }
+}
+
+fn follow_driver(
+    driver: Single<&Transform, (With<Driver>, Without<Camera2d>)>,
+    camera: Single<&mut Transform, With<Camera2d>>,
+) {
+    let mut camera = camera.into_inner();
+    camera.translation = driver.translation.truncate().extend(camera.translation.z);
+}
+
+fn update_lap_display(
+    best_lap: Res<BestLap>,
+    driver: Single<(&LapTimer, &Velocity), With<Driver>>,
+    lap_display: Single<&mut Text, With<LapDisplay>>,
+) {
+    let (timer, vel) = driver.into_inner();
+    let mut text = format!(
+        "lap {}  {}{}\n",
+        timer.laps + 1,
+        lap_time(timer.time),
+        if timer.cut { "  missed a checkpoint" } else { "" },
+    );
+    // This lap's sectors so far, then the rest of the last lap's.
+    let sectors = timer.splits.iter()
+        .scan(0., |start, &end| Some(end - std::mem::replace(start, end)))
+        .map(Some)
+        .chain(timer.last_sectors.iter().skip(timer.splits.len()).map(|_| None));
+    for (i, sector) in sectors.enumerate() {
+        let delta = sector.zip(timer.best_sectors.get(i)).map_or(String::new(), |(sector, best)| format!(" {:+.3}", sector - best));
+        text += &format!("S{} {}{delta}\n", i + 1, lap_time(sector));
+    }
+    text += &format!(
+        "last {}\nbest {}\nrecord {}\n{:.0} km/h",
+        lap_time(timer.last),
+        lap_time(timer.best),
+        lap_time(best_lap.ghost.as_ref().map(|ghost| ghost.time)),
+        vel.length() * 3.6,
+    );
+    lap_display.into_inner().0 = text;
+}
+
+/// Minutes, seconds and thousandths, or dashes if there is no time yet.
+fn lap_time(seconds: Option<f32>) -> String {
+    match seconds {
+        Some(seconds) => format!("{}:{:06.3}", (seconds / 60.) as u32, seconds % 60.),
+        None => "-:--.---".to_string(),
+    }
+}
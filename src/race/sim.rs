@@ -0,0 +1,455 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use crate::fruit::pva::{Position, Velocity, apply_acceleration, apply_velocity};
+use crate::fruit::toa::{Omega, Theta, apply_alpha, apply_omega};
+use crate::race::ai::{AiDriver, RacingLine, Skill, build_racing_line, drive_ai};
+use crate::race::car::{Car, CarControls, CarSpec, Driver, GridSlot, drive_cars};
+use crate::race::collision::{CollisionEvent, solve_collisions};
+use crate::race::ghost::{BestLap, Ghost, GhostRecorder, record_ghost};
+use crate::race::lap::{LapEvent, LapTimer, time_laps};
+use crate::race::track::Track;
+
+/// The fixed-step race simulation. Anything that sets [`CarControls`] should
+/// run `.before(RaceSimSet)`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RaceSimSet;
+
+/// How many fixed ticks the race has run since the last reset.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Eq, Default, Deref)]
+pub struct RaceTick(pub u64);
+
+/// Put every car back on the grid and clear its lap times.
+#[derive(Debug, Event, Clone, Copy, PartialEq, Default)]
+pub struct ResetRaceEvent;
+
+/// How many AI cars line up behind the player.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Eq, Default, Deref)]
+pub struct Opponents(pub usize);
+
+/// Everything the race needs to simulate, and nothing it needs to draw or read
+/// from a keyboard. Works on top of `MinimalPlugins`. Cars are not spawned
+/// here; spawn [`Car`]s with a [`GridSlot`] wherever a race starts.
+pub struct RaceSimPlugin;
+
+impl Plugin for RaceSimPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Track>()
+        .init_resource::<RaceTick>()
+        .init_resource::<BestLap>()
+        .init_resource::<RacingLine>()
+        .init_resource::<Opponents>()
+        .add_systems(FixedUpdate, (
+            reset_cars.run_if(on_event::<ResetRaceEvent>),
+            build_racing_line.run_if(resource_changed::<Track>),
+            apply_velocity,
+            apply_omega,
+            apply_acceleration,
+            apply_alpha,
+            solve_collisions,
+            drive_ai,
+            drive_cars,
+            time_laps,
+            record_ghost,
+            advance_tick,
+        ).chain().in_set(RaceSimSet))
+        .add_event::<ResetRaceEvent>()
+        .add_event::<LapEvent>()
+        .add_event::<CollisionEvent>()
+        ;
+    }
+}
+
+fn advance_tick(
+    mut tick: ResMut<RaceTick>,
+) {
+    tick.0 += 1;
+}
+
+/// The car for grid slot `slot` of `track`, at rest.
+pub fn grid_car(track: &Track, spec: CarSpec, slot: usize) -> (Car, GridSlot, LapTimer) {
+    let (pos, heading) = track.grid(slot);
+    (Car::new(spec, pos, heading), GridSlot(slot), LapTimer::default())
+}
+
+pub fn reset_cars(
+    mut commands: Commands,
+    track: Res<Track>,
+    mut tick: ResMut<RaceTick>,
+    mut resets: EventReader<ResetRaceEvent>,
+    query: Query<(Entity, &CarSpec, &GridSlot, Option<&AiDriver>)>,
+) {
+    resets.clear();
+    for (entity, spec, slot, ai) in &query {
+        let mut car = commands.entity(entity);
+        car.insert(grid_car(&track, *spec, **slot));
+        if let Some(ai) = ai {
+            car.insert(AiDriver::new(ai.skill, ai.seed));
+        }
+    }
+    tick.0 = 0;
+}
+
+pub fn load_driver(
+    mut commands: Commands,
+    track: Res<Track>,
+) {
+    commands.spawn((grid_car(&track, CarSpec::default(), 0), Driver, GhostRecorder::default()));
+}
+
+/// Line the AI cars up behind the player, the best drivers nearest the front.
+pub fn load_opponents(
+    mut commands: Commands,
+    track: Res<Track>,
+    opponents: Res<Opponents>,
+) {
+    for slot in 1..=**opponents {
+        let skill = Skill::from_level(1. - 0.1 * slot as f32);
+        commands.spawn((grid_car(&track, CarSpec::default(), slot), AiDriver::new(skill, slot as u64)));
+    }
+}
+
+/// A snapshot of one car.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CarState {
+    pub entity: Entity,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub theta: f32,
+    pub omega: f32,
+}
+
+/// A headless race with one [`Driver`] car, advancing exactly one fixed tick
+/// per [`RaceSim::step`].
+pub struct RaceSim {
+    app: App,
+}
+
+impl RaceSim {
+    pub fn new(dt: Duration) -> Self {
+        let mut app = App::new();
+        app
+        .add_plugins(MinimalPlugins)
+        .add_plugins(RaceSimPlugin)
+        .insert_resource(Time::<Fixed>::from_duration(dt))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(dt))
+        .add_systems(Startup, load_driver);
+        // The first update only starts the clocks and runs `Startup`.
+        app.update();
+        Self { app }
+    }
+
+    pub fn step(&mut self) {
+        self.app.update();
+    }
+
+    pub fn run(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.step();
+        }
+    }
+
+    /// Put the cars back on the grid on the next step.
+    pub fn reset(&mut self) {
+        self.app.world_mut().send_event(ResetRaceEvent);
+    }
+
+    /// Drive the player car with `controls` from the next step on.
+    pub fn set_controls(&mut self, controls: CarControls) {
+        let world = self.app.world_mut();
+        *world.query_filtered::<&mut CarControls, With<Driver>>().single_mut(world).unwrap() = controls;
+    }
+
+    /// Add an AI car on the next free grid slot, drawing its mistakes from
+    /// `seed`.
+    pub fn add_opponent(&mut self, skill: Skill, seed: u64) -> Entity {
+        let world = self.app.world_mut();
+        let slot = world.query::<&GridSlot>().iter(world).count();
+        let car = grid_car(world.resource::<Track>(), CarSpec::default(), slot);
+        world.spawn((car, AiDriver::new(skill, seed))).id()
+    }
+
+    /// Add a car that nobody drives.
+    pub fn add_car(&mut self, car: Car) -> Entity {
+        self.app.world_mut().spawn(car).id()
+    }
+
+    /// The player car.
+    pub fn car(&mut self) -> CarState {
+        let world = self.app.world_mut();
+        let entity = world.query_filtered::<Entity, With<Driver>>().single(world).unwrap();
+        self.car_state(entity)
+    }
+
+    pub fn car_state(&mut self, entity: Entity) -> CarState {
+        let world = self.app.world_mut();
+        let (pos, vel, theta, omega) = world
+            .query::<(&Position, &Velocity, &Theta, &Omega)>()
+            .get(world, entity)
+            .unwrap();
+        CarState { entity, pos: **pos, vel: **vel, theta: **theta, omega: **omega }
+    }
+
+    /// The player car's lap timer.
+    pub fn lap_timer(&mut self) -> LapTimer {
+        let world = self.app.world_mut();
+        let entity = world.query_filtered::<Entity, With<Driver>>().single(world).unwrap();
+        self.lap_timer_of(entity)
+    }
+
+    pub fn lap_timer_of(&mut self, entity: Entity) -> LapTimer {
+        let world = self.app.world_mut();
+        world.query::<&LapTimer>().get(world, entity).unwrap().clone()
+    }
+
+    pub fn racing_line(&self) -> &RacingLine {
+        self.app.world().resource::<RacingLine>()
+    }
+
+    /// Every collision on the last step.
+    pub fn collisions(&self) -> Vec<CollisionEvent> {
+        self.app.world().resource::<Events<CollisionEvent>>().iter_current_update_events().cloned().collect()
+    }
+
+    /// The fastest lap so far, kept in memory only.
+    pub fn ghost(&self) -> Option<&Ghost> {
+        self.app.world().resource::<BestLap>().ghost.as_ref()
+    }
+
+    pub fn track(&self) -> &Track {
+        self.app.world().resource::<Track>()
+    }
+
+    pub fn tick(&self) -> u64 {
+        **self.app.world().resource::<RaceTick>()
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DT: Duration = Duration::from_micros(15_625);
+
+    fn throttle(throttle: f32, steer: f32) -> CarControls {
+        CarControls { throttle, steer, ..default() }
+    }
+
+    #[test]
+    fn test_car_accelerates_and_brakes() {
+        let mut sim = RaceSim::new(DT);
+        let start = sim.car();
+        sim.set_controls(throttle(1., 0.));
+        sim.run(192);
+        let car = sim.car();
+        assert!(car.vel.length() > 10., "{car:?}");
+        assert!((car.theta - start.theta).abs() < 1e-3);
+        assert!(car.vel.angle_to(Vec2::from_angle(car.theta)).abs() < 1e-3);
+
+        sim.set_controls(CarControls { brake: 1., ..default() });
+        sim.run(64);
+        assert!(sim.car().vel.length() < car.vel.length() / 2.);
+    }
+
+    #[test]
+    fn test_steering_left_turns_left() {
+        let mut sim = RaceSim::new(DT);
+        sim.set_controls(throttle(0.5, 0.));
+        sim.run(128);
+        let before = sim.car();
+        sim.set_controls(throttle(0.5, 1.));
+        sim.run(64);
+        let after = sim.car();
+        assert!(after.theta > before.theta + 0.3, "{before:?} {after:?}");
+        assert!(after.omega > 0.);
+    }
+
+    #[test]
+    fn test_reset_puts_car_on_grid() {
+        let mut sim = RaceSim::new(DT);
+        let start = sim.car();
+        sim.set_controls(throttle(1., 0.3));
+        sim.run(100);
+        sim.reset();
+        sim.step();
+        let car = sim.car();
+        assert!(car.pos.distance(start.pos) < 0.1 && car.vel.length() < 0.1, "{car:?}");
+        let timer = sim.lap_timer();
+        assert_eq!((timer.laps, timer.time, timer.best), (0, None, None));
+    }
+
+    #[test]
+    fn test_reset_repeats_ai() {
+        let mut sim = RaceSim::new(DT);
+        let ai = sim.add_opponent(Skill::from_level(0.5), 3);
+        let drive = |sim: &mut RaceSim| {
+            (0..64 * 10).map(|_| {
+                sim.step();
+                sim.car_state(ai)
+            }).collect::<Vec<_>>()
+        };
+        let first = drive(&mut sim);
+        sim.reset();
+        let second = drive(&mut sim);
+        assert_eq!(first, second);
+    }
+
+    /// Steer for the centreline 12 m ahead at about 15 m/s until the driver
+    /// has done `laps` laps.
+    fn follow_centreline(sim: &mut RaceSim, laps: u32) {
+        for _ in 0..64 * 60 * laps {
+            let car = sim.car();
+            let track = sim.track();
+            let ahead = track.at(track.locate(car.pos).s + 12.);
+            let heading = Vec2::from_angle(car.theta);
+            let steer = heading.angle_to(ahead - car.pos) * 2.;
+            let throttle = if car.vel.length() < 15. { 0.6 } else { 0. };
+            sim.set_controls(CarControls { throttle, steer: steer.clamp(-1., 1.), ..default() });
+            sim.step();
+            let car = sim.car();
+            assert!(sim.track().on_road(car.pos), "left the road at {car:?}");
+            if sim.lap_timer().laps == laps {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn test_following_the_centreline_laps() {
+        let mut sim = RaceSim::new(DT);
+        let length = sim.track().length();
+        follow_centreline(&mut sim, 2);
+        let timer = sim.lap_timer();
+        assert_eq!(timer.laps, 2);
+        // Around 15 m/s all the way round.
+        let lap = timer.last.unwrap();
+        assert!(lap > length / 16. && lap < length / 10., "lap of {length} m in {lap} s");
+        assert_eq!(timer.last_sectors.len(), sim.track().checkpoints.len() + 1);
+        assert!((timer.last_sectors.iter().sum::<f32>() - lap).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_best_lap_becomes_ghost() {
+        let mut sim = RaceSim::new(DT);
+        assert_eq!(sim.ghost(), None);
+        follow_centreline(&mut sim, 1);
+        let timer = sim.lap_timer();
+        let ghost = sim.ghost().unwrap();
+        assert_eq!(Some(ghost.time), timer.best);
+        assert_eq!(ghost.frames.len(), (ghost.time / DT.as_secs_f32()).round() as usize);
+        // The ghost starts just over the line.
+        let start = Vec2::from(ghost.frames[0].pos);
+        assert!(sim.track().locate(start).s < 1.);
+    }
+
+    #[test]
+    fn test_ai_laps_on_its_own() {
+        let mut sim = RaceSim::new(DT);
+        let length = sim.track().length();
+        let ai = sim.add_opponent(Skill::from_level(1.), 1);
+        for _ in 0..64 * 120 {
+            sim.step();
+            let car = sim.car_state(ai);
+            assert!(sim.track().on_road(car.pos), "left the road at {car:?}");
+            if sim.lap_timer_of(ai).laps == 2 {
+                break;
+            }
+        }
+        let timer = sim.lap_timer_of(ai);
+        assert_eq!(timer.laps, 2);
+        // Quicker than cruising round the middle.
+        let lap = timer.last.unwrap();
+        assert!(lap < length / 20., "lap of {length} m in {lap} s");
+    }
+
+    #[test]
+    fn test_ai_overtakes_without_touching() {
+        let mut sim = RaceSim::new(DT);
+        // The slow car starts in front.
+        let slow = sim.add_opponent(Skill { pace: 0.5, ..Skill::from_level(1.) }, 1);
+        let fast = sim.add_opponent(Skill::from_level(1.), 2);
+        let mut closest = f32::INFINITY;
+        for _ in 0..64 * 60 {
+            sim.step();
+            let (a, b) = (sim.car_state(slow), sim.car_state(fast));
+            closest = closest.min(a.pos.distance(b.pos));
+        }
+        let (slow, fast) = (sim.lap_timer_of(slow), sim.lap_timer_of(fast));
+        assert!(fast.laps > slow.laps, "{fast:?} {slow:?}");
+        assert!(closest > CarSpec::default().width + 0.5, "came within {closest} m");
+    }
+
+    #[test]
+    fn test_barrier_stops_car() {
+        let mut sim = RaceSim::new(DT);
+        let track = sim.track().clone();
+        let spec = CarSpec::default();
+        // Straight at the barrier on the right of the front straight.
+        let s = 40.;
+        let (segment, _) = track.segment_at(s);
+        let right = -track.normal(segment);
+        let wall = track.barriers.iter()
+            .flat_map(|barrier| &barrier.points)
+            .map(|&point| track.locate(point))
+            .filter(|point| (point.s - s).abs() < 5. && point.offset < 0.)
+            .map(|point| point.offset)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let mut car = Car::new(spec, track.at(s), right.to_angle());
+        car.vel.0 = right * 30.;
+        let entity = sim.add_car(car);
+        let mut hit = false;
+        for _ in 0..64 * 4 {
+            sim.step();
+            hit |= sim.collisions().iter().any(|collision| collision.car == entity && collision.other.is_none());
+            let car = sim.car_state(entity);
+            let offset = track.locate(car.pos).offset;
+            assert!(offset > wall + spec.width / 2. - 0.1, "through the barrier at {offset}: {car:?}");
+        }
+        assert!(hit);
+        let car = sim.car_state(entity);
+        assert!(car.vel.dot(right) <= 0.1, "{car:?}");
+    }
+
+    #[test]
+    fn test_side_impact_spins_car() {
+        let mut sim = RaceSim::new(DT);
+        let spec = CarSpec::default();
+        let driver = sim.car();
+        let heading = Vec2::from_angle(driver.theta);
+        // Into the left side of the driver, behind the middle.
+        let pos = driver.pos - heading * 1.5 + heading.perp() * (spec.width / 2. + spec.length / 2. + 0.3);
+        let mut car = Car::new(spec, pos, (-heading.perp()).to_angle());
+        car.vel.0 = -heading.perp() * 10.;
+        let other = sim.add_car(car);
+        let mut spin: f32 = 0.;
+        let mut impulse = 0.;
+        for _ in 0..64 {
+            sim.step();
+            spin = spin.max(sim.car().omega);
+            for collision in sim.collisions() {
+                if impulse == 0. {
+                    // Where the nose went in.
+                    let points = &collision.points;
+                    assert!(points.iter().all(|point| point.distance(driver.pos - heading * 1.5) < spec.width), "{points:?}");
+                }
+                impulse += collision.impulse;
+            }
+        }
+        // The tail is shoved right, so the nose swings left.
+        assert!(spin > 0.3, "{spin}");
+        assert!(impulse > spec.mass * 5., "{impulse}");
+        let (a, b) = (sim.car(), sim.car_state(other));
+        assert!(a.pos.distance(b.pos) > (spec.width + spec.length) / 2. - 0.1, "{a:?} {b:?}");
+    }
+}
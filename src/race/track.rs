@@ -0,0 +1,691 @@
+use std::io;
+
+use bevy::asset::{AssetLoader, LoadContext, RenderAssetUsages, io::Reader};
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use serde::{Deserialize, Serialize};
+
+use crate::race::car::Surface;
+use crate::race::sim::ResetRaceEvent;
+
+/// How far behind the start line, in metres, the first car on the grid sits.
+const GRID_GAP: f32 = 8.;
+/// Roughly how far apart, in metres, the centreline is sampled.
+const SAMPLE_SPACING: f32 = 2.;
+/// How thick barriers are drawn.
+pub(crate) const BARRIER_WIDTH: f32 = 0.8;
+
+/// A closed circuit as written in a `.track.ron` file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackFile {
+    pub name: String,
+    /// The centreline runs through these, anticlockwise and back to the first.
+    pub points: Vec<ControlPoint>,
+    /// The point the start/finish line crosses.
+    #[serde(default)]
+    pub start: usize,
+    /// Points that a lap has to pass, in any order. Laps only have to get
+    /// halfway round if left out.
+    #[serde(default)]
+    pub checkpoints: Vec<usize>,
+    /// How far beyond the edge of the road the barriers on either side stand.
+    /// No barriers if left out.
+    #[serde(default)]
+    pub runoff: Option<f32>,
+    /// What's off the road where no zone says otherwise.
+    #[serde(default)]
+    pub verge: Ground,
+    /// Patches of ground that aren't the road or the verge. Later zones cover
+    /// earlier ones and the road.
+    #[serde(default)]
+    pub zones: Vec<SurfaceZone>,
+    /// Extra barriers, each an open line through its points.
+    #[serde(default)]
+    pub walls: Vec<Vec<(f32, f32)>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ControlPoint {
+    pub pos: (f32, f32),
+    /// Width of the road here. It changes smoothly on the way to the next
+    /// point.
+    pub width: f32,
+}
+
+/// A polygon of some other ground, as written in a `.track.ron` file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SurfaceZone {
+    pub ground: Ground,
+    pub points: Vec<(f32, f32)>,
+}
+
+/// What a car can be driving on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Ground {
+    Asphalt,
+    #[default]
+    Grass,
+    Gravel,
+}
+
+/// A closed circuit, built from a [`TrackFile`]. The race starts from a copy of
+/// `assets/tracks/stadium.track.ron` built into the binary and swaps in the
+/// picked track once it loads, and again whenever the file changes.
+#[derive(Debug, Asset, Resource, TypePath, Clone, PartialEq)]
+pub struct Track {
+    pub name: String,
+    /// The centreline, sampled every couple of metres, anticlockwise from the
+    /// start/finish line and closing back on the first point.
+    pub points: Vec<Vec2>,
+    /// Width of the road at each of `points`.
+    pub widths: Vec<f32>,
+    /// Distance along the centreline to each checkpoint, in lap order. There's
+    /// always at least one.
+    pub checkpoints: Vec<f32>,
+    pub verge: Ground,
+    pub zones: Vec<Zone>,
+    pub barriers: Vec<Barrier>,
+}
+
+/// A polygon of some other ground.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub ground: Ground,
+    pub points: Vec<Vec2>,
+}
+
+/// A line cars can't cross.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Barrier {
+    pub points: Vec<Vec2>,
+    /// Whether the last point joins back up with the first.
+    pub closed: bool,
+}
+
+/// Where something is relative to the centreline.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TrackPoint {
+    /// Distance along the centreline from the start line, from 0 up to the
+    /// length of the lap.
+    pub s: f32,
+    /// Distance from the centreline, positive to the left.
+    pub offset: f32,
+    /// The centreline segment from `points[segment]` to the point after it.
+    pub segment: usize,
+    /// Width of the road there.
+    pub width: f32,
+}
+
+/// The track asset the race is using.
+#[derive(Debug, Resource, Clone, Deref)]
+pub struct TrackHandle(pub Handle<Track>);
+
+/// The path of the track to race on, relative to the asset folder.
+#[derive(Debug, Resource, Clone, PartialEq, Eq, Deref)]
+pub struct TrackPath(pub String);
+
+/// The road, rebuilt whenever the [`Track`] changes.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Default)]
+pub struct TrackMesh;
+
+#[derive(Default)]
+pub struct TrackLoader;
+
+impl Default for Track {
+    fn default() -> Self {
+        Track::parse(include_str!("../../assets/tracks/stadium.track.ron")).expect("built-in track")
+    }
+}
+
+impl Ground {
+    pub fn surface(self) -> Surface {
+        match self {
+            Ground::Asphalt => Surface::ASPHALT,
+            Ground::Grass => Surface::GRASS,
+            Ground::Gravel => Surface::GRAVEL,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Ground::Asphalt => Color::srgb(0.3, 0.3, 0.32),
+            Ground::Grass => Color::srgb(0.25, 0.45, 0.2),
+            Ground::Gravel => Color::srgb(0.7, 0.62, 0.45),
+        }
+    }
+}
+
+impl Barrier {
+    /// Every straight piece of the barrier in order.
+    pub fn segments(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let end = if self.closed { self.points.len() } else { self.points.len() - 1 };
+        (0..end).map(|i| (self.points[i], self.points[(i + 1) % self.points.len()]))
+    }
+
+    pub fn to_mesh(&self) -> Mesh {
+        strip_mesh(&self.points, &vec![BARRIER_WIDTH / 2.; self.points.len()], self.closed)
+    }
+}
+
+impl Track {
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let file: TrackFile = ron::from_str(text).map_err(io::Error::other)?;
+        Track::build(file)
+    }
+
+    /// Check `file` and lay the road along a Catmull-Rom spline through its
+    /// points.
+    pub fn build(file: TrackFile) -> io::Result<Self> {
+        let error = |problem: String| Err(io::Error::other(format!("{} {problem}", file.name)));
+        let n = file.points.len();
+        if n < 3 {
+            return error("needs at least three points".to_string());
+        }
+        if let Some(i) = file.points.iter().position(|point| point.width.is_nan() || point.width <= 0.) {
+            return error(format!("needs a positive width at point {i}"));
+        }
+        if (0..n).any(|i| file.points[i].pos == file.points[(i + 1) % n].pos) {
+            return error("repeats a point".to_string());
+        }
+        if file.start >= n {
+            return error(format!("starts at point {} of {n}", file.start));
+        }
+        for (i, &checkpoint) in file.checkpoints.iter().enumerate() {
+            if checkpoint >= n || checkpoint == file.start || file.checkpoints[..i].contains(&checkpoint) {
+                return error(format!("can't have a checkpoint at point {checkpoint}"));
+            }
+        }
+        if file.runoff.is_some_and(|runoff| runoff.is_nan() || runoff < 0.) {
+            return error("needs a runoff of at least zero".to_string());
+        }
+        let zones: Vec<_> = file.zones.iter()
+            .map(|zone| Zone { ground: zone.ground, points: zone.points.iter().copied().map(Vec2::from).collect() })
+            .collect();
+        if let Some(i) = zones.iter().position(|zone| triangulate(&zone.points).is_none()) {
+            return error(format!("needs zone {i} to be a polygon that doesn't cross itself"));
+        }
+        if let Some(i) = file.walls.iter().position(|wall| wall.len() < 2) {
+            return error(format!("needs at least two points on wall {i}"));
+        }
+
+        // Go round from the start line so that distances along the track count
+        // from there.
+        let control: Vec<_> = (0..n).map(|i| file.points[(file.start + i) % n]).collect();
+        let curve = CubicCardinalSpline::new_catmull_rom(control.iter().map(|point| Vec2::from(point.pos)))
+            .to_curve_cyclic()
+            .map_err(io::Error::other)?;
+        let mut points = Vec::new();
+        let mut widths = Vec::new();
+        let mut control_s = Vec::with_capacity(n);
+        let mut s = 0.;
+        for i in 0..n {
+            let estimate: f32 = (0..16)
+                .map(|k| curve.position(i as f32 + k as f32 / 16.).distance(curve.position(i as f32 + (k + 1) as f32 / 16.)))
+                .sum();
+            let samples = (estimate / SAMPLE_SPACING).ceil().max(1.) as usize;
+            let (width, next_width) = (control[i].width, control[(i + 1) % n].width);
+            for k in 0..samples {
+                let t = k as f32 / samples as f32;
+                let point = curve.position(i as f32 + t);
+                if let Some(&last) = points.last() {
+                    s += point.distance(last);
+                }
+                if k == 0 {
+                    control_s.push(s);
+                }
+                points.push(point);
+                widths.push(width + (next_width - width) * t * t * (3. - 2. * t));
+            }
+        }
+        let mut checkpoints: Vec<_> = file.checkpoints.iter().map(|&i| control_s[(i + n - file.start) % n]).collect();
+        checkpoints.sort_by(f32::total_cmp);
+
+        let mut track = Track {
+            name: file.name,
+            points,
+            widths,
+            checkpoints,
+            verge: file.verge,
+            zones,
+            barriers: Vec::new(),
+        };
+        if track.checkpoints.is_empty() {
+            track.checkpoints.push(track.length() / 2.);
+        }
+        if let Some(runoff) = file.runoff {
+            for side in [1., -1.] {
+                let points = (0..track.points.len())
+                    .map(|i| track.points[i] + track.normal(i) * side * (track.widths[i] / 2. + runoff))
+                    .collect();
+                track.barriers.push(Barrier { points, closed: true });
+            }
+        }
+        track.barriers.extend(file.walls.into_iter().map(|wall| Barrier {
+            points: wall.into_iter().map(Vec2::from).collect(),
+            closed: false,
+        }));
+        Ok(track)
+    }
+
+    /// A hash of the road, ground and barriers, which tells apart tracks that
+    /// share a name, or one track before and after an edit. FNV-1a, so it stays
+    /// the same from one build to the next.
+    pub fn geometry(&self) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325;
+        let mut add = |word: u32| {
+            for byte in word.to_le_bytes() {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        let add_points = |add: &mut dyn FnMut(u32), points: &[Vec2]| {
+            add(points.len() as u32);
+            for point in points {
+                add(point.x.to_bits());
+                add(point.y.to_bits());
+            }
+        };
+        add_points(&mut add, &self.points);
+        for &width in &self.widths {
+            add(width.to_bits());
+        }
+        add(self.checkpoints.len() as u32);
+        for &checkpoint in &self.checkpoints {
+            add(checkpoint.to_bits());
+        }
+        add(self.verge as u32);
+        add(self.zones.len() as u32);
+        for zone in &self.zones {
+            add(zone.ground as u32);
+            add_points(&mut add, &zone.points);
+        }
+        add(self.barriers.len() as u32);
+        for barrier in &self.barriers {
+            add(barrier.closed as u32);
+            add_points(&mut add, &barrier.points);
+        }
+        hash
+    }
+
+    /// Every centreline segment in order, the last closing the loop.
+    pub fn segments(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        self.points.iter().zip(self.points.iter().cycle().skip(1)).map(|(&a, &b)| (a, b))
+    }
+
+    /// Length of one lap along the centreline.
+    pub fn length(&self) -> f32 {
+        self.segments().map(|(a, b)| a.distance(b)).sum()
+    }
+
+    /// Unit vector pointing left across the road at `points[i]`.
+    pub fn normal(&self, i: usize) -> Vec2 {
+        let n = self.points.len();
+        (self.points[(i + 1) % n] - self.points[(i + n - 1) % n]).normalize().perp()
+    }
+
+    /// The centreline point nearest to `pos`.
+    pub fn locate(&self, pos: Vec2) -> TrackPoint {
+        let n = self.points.len();
+        let mut nearest = TrackPoint::default();
+        let mut nearest_distance = f32::INFINITY;
+        let mut s = 0.;
+        for (segment, (a, b)) in self.segments().enumerate() {
+            let along = b - a;
+            let length = along.length();
+            let t = ((pos - a).dot(along) / (length * length)).clamp(0., 1.);
+            let foot = a + along * t;
+            let distance = pos.distance_squared(foot);
+            if distance < nearest_distance {
+                nearest_distance = distance;
+                nearest = TrackPoint {
+                    s: s + length * t,
+                    offset: (along / length).perp_dot(pos - a),
+                    segment,
+                    width: self.widths[segment].lerp(self.widths[(segment + 1) % n], t),
+                };
+            }
+            s += length;
+        }
+        nearest
+    }
+
+    /// The centreline point `s` along from the start line, wrapping round.
+    pub fn at(&self, s: f32) -> Vec2 {
+        let (segment, t) = self.segment_at(s);
+        self.points[segment].lerp(self.points[(segment + 1) % self.points.len()], t)
+    }
+
+    /// Which centreline segment is `s` along from the start line, wrapping
+    /// round, and how far along it from 0 to 1.
+    pub fn segment_at(&self, s: f32) -> (usize, f32) {
+        let mut s = s.rem_euclid(self.length());
+        for (segment, (a, b)) in self.segments().enumerate() {
+            let length = a.distance(b);
+            if s <= length {
+                return (segment, s / length);
+            }
+            s -= length;
+        }
+        (0, 0.)
+    }
+
+    pub fn on_road(&self, pos: Vec2) -> bool {
+        let point = self.locate(pos);
+        point.offset.abs() <= point.width / 2.
+    }
+
+    /// What's underfoot at `pos`.
+    pub fn ground(&self, pos: Vec2) -> Ground {
+        if let Some(zone) = self.zones.iter().rev().find(|zone| contains(&zone.points, pos)) {
+            zone.ground
+        } else if self.on_road(pos) {
+            Ground::Asphalt
+        } else {
+            self.verge
+        }
+    }
+
+    /// Where car `slot` lines up before the start, and the way it faces. Cars
+    /// are staggered left and right in a column behind the line.
+    pub fn grid(&self, slot: usize) -> (Vec2, f32) {
+        let (start, next) = (self.points[0], self.points[1]);
+        let forward = (next - start).normalize();
+        let side = if slot.is_multiple_of(2) { 1. } else { -1. } * self.widths[0] / 4.;
+        let back = GRID_GAP * (slot + 1) as f32;
+        (start - forward * back + forward.perp() * side, forward.to_angle())
+    }
+
+    /// The road as a strip of triangles.
+    pub fn to_mesh(&self) -> Mesh {
+        let half_widths: Vec<_> = self.widths.iter().map(|width| width / 2.).collect();
+        strip_mesh(&self.points, &half_widths, true)
+    }
+}
+
+impl Zone {
+    pub fn to_mesh(&self) -> Mesh {
+        let positions: Vec<_> = self.points.iter().map(|point| point.extend(0.).to_array()).collect();
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+            .with_inserted_indices(Indices::U32(triangulate(&self.points).unwrap_or_default()))
+    }
+}
+
+/// A band `half_widths` either side of a line through `points`, mitred at
+/// every corner.
+fn strip_mesh(points: &[Vec2], half_widths: &[f32], closed: bool) -> Mesh {
+    let n = points.len();
+    let mut positions = Vec::with_capacity(2 * n);
+    for i in 0..n {
+        let here = points[i];
+        let before = if closed || i > 0 { points[(i + n - 1) % n] } else { here };
+        let after = if closed || i + 1 < n { points[(i + 1) % n] } else { here };
+        let normal_in = (here - before).normalize_or_zero().perp();
+        let normal_out = (after - here).normalize_or_zero().perp();
+        let miter = (normal_in + normal_out).normalize();
+        let square = if normal_out == Vec2::ZERO { normal_in } else { normal_out };
+        let reach = half_widths[i] / miter.dot(square).max(0.25);
+        positions.push((here + miter * reach).extend(0.).to_array());
+        positions.push((here - miter * reach).extend(0.).to_array());
+    }
+    let quads = if closed { n } else { n - 1 };
+    let mut indices = Vec::with_capacity(6 * quads);
+    for i in 0..quads as u32 {
+        let j = (i + 1) % n as u32;
+        let (left, right, next_left, next_right) = (2 * i, 2 * i + 1, 2 * j, 2 * j + 1);
+        indices.extend([left, right, next_right, left, next_right, next_left]);
+    }
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+/// Whether `pos` is inside the polygon through `points`.
+fn contains(points: &[Vec2], pos: Vec2) -> bool {
+    let mut inside = false;
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        if (a.y > pos.y) != (b.y > pos.y) && pos.x < a.x + (pos.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Cut the polygon through `points` into triangles by clipping ears, or `None`
+/// if it crosses itself.
+fn triangulate(points: &[Vec2]) -> Option<Vec<u32>> {
+    if points.len() < 3 {
+        return None;
+    }
+    let area: f32 = (0..points.len()).map(|i| points[i].perp_dot(points[(i + 1) % points.len()])).sum();
+    let mut left: Vec<usize> = (0..points.len()).collect();
+    if area < 0. {
+        left.reverse();
+    }
+    let mut indices = Vec::with_capacity(3 * (points.len() - 2));
+    while left.len() > 3 {
+        let m = left.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (points[left[(i + m - 1) % m]], points[left[i]], points[left[(i + 1) % m]]);
+            (b - a).perp_dot(c - b) > 0.
+                && left.iter().all(|&k| {
+                    let p = points[k];
+                    p == a || p == b || p == c
+                        || (b - a).perp_dot(p - a) < 0. || (c - b).perp_dot(p - b) < 0. || (a - c).perp_dot(p - c) < 0.
+                })
+        })?;
+        indices.extend([left[(ear + m - 1) % m], left[ear], left[(ear + 1) % m]].map(|k| k as u32));
+        left.remove(ear);
+    }
+    indices.extend(left.iter().map(|&k| k as u32));
+    let clipped: f32 = indices.chunks(3)
+        .map(|t| (points[t[1] as usize] - points[t[0] as usize]).perp_dot(points[t[2] as usize] - points[t[0] as usize]).abs())
+        .sum();
+    // Ears of a polygon that crosses itself overlap and add up to too much.
+    (clipped <= area.abs() * 1.001).then_some(indices)
+}
+
+impl AssetLoader for TrackLoader {
+    type Asset = Track;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Track, io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let text = String::from_utf8(bytes).map_err(io::Error::other)?;
+        Track::parse(&text)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["track.ron"]
+    }
+}
+
+pub fn load_track(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    path: Res<TrackPath>,
+) {
+    commands.insert_resource(TrackHandle(asset_server.load(path.0.clone())));
+}
+
+/// Swap in the track asset whenever it finishes loading or is edited, and
+/// put the cars back on its grid.
+pub fn update_track(
+    mut events: EventReader<AssetEvent<Track>>,
+    handle: Res<TrackHandle>,
+    assets: Res<Assets<Track>>,
+    mut track: ResMut<Track>,
+    mut resets: EventWriter<ResetRaceEvent>,
+) {
+    for event in events.read() {
+        if (event.is_loaded_with_dependencies(handle.id()) || event.is_modified(handle.id()))
+            && let Some(loaded) = assets.get(handle.id())
+        {
+            info!("loaded track {}", loaded.name);
+            if track.set_if_neq(loaded.clone()) {
+                resets.write(ResetRaceEvent);
+            }
+        }
+    }
+}
+
+/// Draw the road, the zones, the start line and the barriers, again whenever
+/// the [`Track`] changes.
+pub fn load_track_mesh(
+    mut commands: Commands,
+    track: Res<Track>,
+    mut clear_color: ResMut<ClearColor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    existing: Query<Entity, With<TrackMesh>>,
+) {
+    for entity in &existing {
+        commands.entity(entity).despawn();
+    }
+    clear_color.0 = track.verge.color();
+    commands.spawn((
+        TrackMesh,
+        Mesh2d(meshes.add(track.to_mesh())),
+        MeshMaterial2d(materials.add(Ground::Asphalt.color())),
+        Transform::default(),
+    ));
+    for zone in &track.zones {
+        commands.spawn((
+            TrackMesh,
+            Mesh2d(meshes.add(zone.to_mesh())),
+            MeshMaterial2d(materials.add(zone.ground.color())),
+            Transform::from_xyz(0., 0., 0.05),
+        ));
+    }
+    let (start, next) = (track.points[0], track.points[1]);
+    commands.spawn((
+        TrackMesh,
+        Mesh2d(meshes.add(Rectangle::new(1., track.widths[0]))),
+        MeshMaterial2d(materials.add(Color::WHITE)),
+        Transform::from_translation(start.extend(0.1))
+            .with_rotation(Quat::from_rotation_z((next - start).to_angle())),
+    ));
+    let barrier_material = materials.add(Color::srgb(0.85, 0.85, 0.8));
+    for barrier in &track.barriers {
+        commands.spawn((
+            TrackMesh,
+            Mesh2d(meshes.add(barrier.to_mesh())),
+            MeshMaterial2d(barrier_material.clone()),
+            Transform::from_xyz(0., 0., 0.2),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_locate_on_square() {
+        let track = Track {
+            name: "square".to_string(),
+            points: vec![Vec2::new(0., 0.), Vec2::new(100., 0.), Vec2::new(100., 100.), Vec2::new(0., 100.)],
+            widths: vec![10., 10., 6., 6.],
+            checkpoints: Vec::new(),
+            verge: Ground::Grass,
+            zones: vec![Zone { ground: Ground::Gravel, points: vec![Vec2::new(40., -20.), Vec2::new(60., -20.), Vec2::new(50., -2.)] }],
+            barriers: Vec::new(),
+        };
+        assert_eq!(track.length(), 400.);
+
+        let near = |pos: Vec2, s: f32, offset: f32, segment: usize| {
+            let point = track.locate(pos);
+            assert!((point.s - s).abs() < 1e-3 && (point.offset - offset).abs() < 1e-3, "{point:?}");
+            assert_eq!(point.segment, segment);
+        };
+        near(Vec2::new(30., 4.), 30., 4., 0);
+        near(Vec2::new(103., 50.), 150., -3., 1);
+        near(Vec2::new(-2., 10.), 390., -2., 3);
+
+        assert!(track.on_road(Vec2::new(50., -5.)));
+        assert!(!track.on_road(Vec2::new(50., 6.)));
+        // Narrower at the far corners.
+        assert!(!track.on_road(Vec2::new(104., 60.)));
+        assert_eq!(track.ground(Vec2::new(50., -3.)), Ground::Gravel);
+        assert_eq!(track.ground(Vec2::new(30., -3.)), Ground::Asphalt);
+        assert_eq!(track.ground(Vec2::new(50., 50.)), Ground::Grass);
+        assert_eq!(track.at(150.), Vec2::new(100., 50.));
+        assert!(track.at(-10.).distance(Vec2::new(0., 10.)) < 1e-3);
+        let (pos, heading) = track.grid(0);
+        assert!(pos.x < 0. && heading == 0.);
+    }
+
+    #[test]
+    fn test_built_in_track() {
+        let track = Track::default();
+        assert!(track.length() > 500.);
+        // The road goes through the control points, starting from `start`.
+        assert!(track.points[0].distance(Vec2::new(0., -60.)) < 1e-3);
+        assert!(track.locate(Vec2::new(160., 0.)).offset.abs() < 0.1);
+        let spacing = track.points.windows(2).map(|pair| pair[0].distance(pair[1])).fold(0., f32::max);
+        assert!(spacing < 1.5 * SAMPLE_SPACING, "{spacing}");
+        assert_eq!(track.checkpoints.len(), 2);
+        assert!((track.checkpoints[0] - track.locate(Vec2::new(160., 0.)).s).abs() < 0.1);
+        assert!((track.checkpoints[1] - track.locate(Vec2::new(-160., 0.)).s).abs() < 0.1);
+        assert!(track.on_road(track.grid(5).0));
+
+        assert_eq!(track.ground(Vec2::new(0., -60.)), Ground::Asphalt);
+        assert_eq!(track.ground(Vec2::new(0., 0.)), Ground::Grass);
+        assert_eq!(track.ground(Vec2::new(178., 0.)), Ground::Gravel);
+        // Barriers either side, clear of the road.
+        assert_eq!(track.barriers.len(), 2);
+        for barrier in &track.barriers {
+            assert!(barrier.points.iter().all(|&point| !track.on_road(point)));
+        }
+    }
+
+    #[test]
+    fn test_geometry() {
+        let track = Track::default();
+        assert_eq!(track.geometry(), Track::default().geometry());
+        let renamed = Track { name: "Renamed".to_string(), ..track.clone() };
+        assert_eq!(renamed.geometry(), track.geometry());
+        let mut moved = track.clone();
+        moved.barriers[0].points[0].x += 0.1;
+        assert_ne!(moved.geometry(), track.geometry());
+        let mut wider = track.clone();
+        wider.widths[3] += 1.;
+        assert_ne!(wider.geometry(), track.geometry());
+    }
+
+    #[test]
+    fn test_bad_tracks() {
+        let track = |rest: &str| Track::parse(&format!(
+            "(name: \"bad\", points: [(pos: (0.0, 0.0), width: 10.0), (pos: (100.0, 0.0), width: 10.0), (pos: (50.0, 80.0), width: 10.0)], {rest})",
+        ));
+        assert!(track("").is_ok());
+        assert!(track("runoff: Some(5.0), walls: [[(0.0, -20.0), (100.0, -20.0)]]").is_ok());
+        assert!(Track::parse(r#"(name: "line", points: [(pos: (0.0, 0.0), width: 10.0), (pos: (1.0, 0.0), width: 10.0)])"#).is_err());
+        assert!(track("start: 3").is_err());
+        assert!(track("checkpoints: [1, 1]").is_err());
+        assert!(track("checkpoints: [0]").is_err());
+        assert!(track("zones: [(ground: Gravel, points: [(0.0, 0.0), (10.0, 10.0), (10.0, 0.0), (0.0, 10.0)])]").is_err());
+        assert!(track("walls: [[(0.0, 0.0)]]").is_err());
+    }
+
+    #[test]
+    fn test_triangulate_concave() {
+        // An L, which has a corner no fan from the first point can reach.
+        let points = [(0., 0.), (20., 0.), (20., 10.), (10., 10.), (10., 20.), (0., 20.)].map(Vec2::from);
+        let indices = triangulate(&points).unwrap();
+        assert_eq!(indices.len(), 12);
+        let area: f32 = indices.chunks(3)
+            .map(|t| (points[t[1] as usize] - points[t[0] as usize]).perp_dot(points[t[2] as usize] - points[t[0] as usize]) / 2.)
+            .sum();
+        assert_eq!(area, 300.);
+        assert!(contains(&points, Vec2::new(5., 15.)));
+        assert!(!contains(&points, Vec2::new(15., 15.)));
+    }
+}